@@ -0,0 +1,30 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+fn main() {
+    let target = env::var("TARGET").unwrap();
+
+    println!("cargo:rustc-check-cfg=cfg(has_itm)");
+    println!("cargo:rustc-check-cfg=cfg(has_fault_status)");
+
+    // the ITM isn't available on armv6-m and armv8-m baseline
+    if !target.starts_with("thumbv6m-") && !target.starts_with("thumbv8m.base-") {
+        println!("cargo:rustc-cfg=has_itm");
+    }
+
+    // the examples for bare metal targets are linked with the linker script of `cortex-m-rt`,
+    // which needs the memory layout in `memory.x`
+    if env::var("CARGO_CFG_TARGET_OS").unwrap() == "none" {
+        let out = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+        fs::copy("examples/memory.x", out.join("memory.x")).unwrap();
+        println!("cargo:rustc-link-search={}", out.display());
+        println!("cargo:rustc-link-arg-examples=-Tlink.x");
+    }
+    println!("cargo:rerun-if-changed=examples/memory.x");
+
+    // armv6-m has no fault status registers
+    if !target.starts_with("thumbv6m-") {
+        println!("cargo:rustc-cfg=has_fault_status");
+    }
+}
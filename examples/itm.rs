@@ -0,0 +1,36 @@
+//! Write panics to ITM stimulus port 0, to be read over SWO with a debug probe
+//!
+//! ```text
+//! cargo build --example itm --features cortex-m --target thumbv7em-none-eabihf
+//! ```
+//!
+//! The debugger has to enable the ITM and SWO output, e.g. with `probe-rs` or OpenOCD. On the host
+//! the example is empty.
+
+#![cfg_attr(target_os = "none", no_std, no_main)]
+
+#[cfg(all(target_os = "none", not(has_itm)))]
+compile_error!("the ITM isn't available on armv6-m and armv8-m baseline");
+
+#[cfg(all(target_os = "none", has_itm))]
+mod bare_metal {
+    use core::pin::pin;
+    use cortex_m_rt::entry;
+    use panic_write::writers::ItmWriter;
+    use panic_write::PanicHandler;
+
+    #[entry]
+    fn main() -> ! {
+        let mut peripherals = cortex_m::Peripherals::take().unwrap();
+
+        let handler = pin!(PanicHandler::new(ItmWriter::new(
+            &mut peripherals.ITM.stim[0]
+        )));
+        let _registration = handler.register();
+
+        panic!("boom");
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+fn main() {}
@@ -0,0 +1,6 @@
+/* The memory layout of the lm3s6965 emulated by QEMU, used to link the examples */
+MEMORY
+{
+  FLASH : ORIGIN = 0x00000000, LENGTH = 256K
+  RAM : ORIGIN = 0x20000000, LENGTH = 64K
+}
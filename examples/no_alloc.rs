@@ -0,0 +1,39 @@
+//! Register a handler and write a simulated panic without `std` or a global allocator
+//!
+//! Built for a bare metal target this fails to link if the crate starts to allocate, because
+//! there is no allocator to link against:
+//!
+//! ```text
+//! cargo build --example no_alloc --features no-alloc-test --target thumbv7em-none-eabihf
+//! ```
+//!
+//! The `no_alloc` test does this as part of the test suite. On the host the example is empty.
+
+#![cfg_attr(target_os = "none", no_std, no_main)]
+
+#[cfg(target_os = "none")]
+mod bare_metal {
+    use core::pin::pin;
+    use cortex_m_rt::entry;
+    use panic_write::writers::SliceWriter;
+    use panic_write::PanicHandler;
+
+    #[entry]
+    fn main() -> ! {
+        let mut buffer = [0; 64];
+        let handler = pin!(PanicHandler::new(SliceWriter::new(&mut buffer)));
+        let registration = handler.register();
+        panic_write::simulate_panic_with(format_args!("boom"), None);
+        // keep the output so the writing isn't optimized out
+        core::hint::black_box(registration.detach().written());
+        loop {}
+    }
+
+    #[panic_handler]
+    fn panic(_info: &core::panic::PanicInfo) -> ! {
+        loop {}
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+fn main() {}
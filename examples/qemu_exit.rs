@@ -0,0 +1,33 @@
+//! Exit QEMU with a failing status when the program panics, e.g. to end a test run
+//!
+//! ```text
+//! cargo build --example qemu_exit --features semihosting --target thumbv7m-none-eabi
+//! qemu-system-arm -machine lm3s6965evb -nographic -semihosting-config enable=on,target=native \
+//!     -kernel target/thumbv7m-none-eabi/debug/examples/qemu_exit
+//! echo $? # 1
+//! ```
+//!
+//! On the host the example is empty.
+
+#![cfg_attr(target_os = "none", no_std, no_main)]
+
+#[cfg(target_os = "none")]
+mod bare_metal {
+    use core::pin::pin;
+    use cortex_m_rt::entry;
+    use panic_write::writers::SemihostingWriter;
+    use panic_write::{set_action, PanicAction, PanicHandler};
+
+    #[entry]
+    fn main() -> ! {
+        set_action(PanicAction::SemihostExit(1));
+
+        let handler = pin!(PanicHandler::new(SemihostingWriter::stdout()));
+        let _registration = handler.register();
+
+        panic!("this exits qemu with status 1");
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+fn main() {}
@@ -0,0 +1,48 @@
+//! Write panics to a uart and reset the system once the message is out
+//!
+//! ```text
+//! cargo build --example reset --features cortex-m --target thumbv7em-none-eabihf
+//! ```
+//!
+//! The uart is the one of the lm3s6965 that QEMU emulates, on real hardware it would come from
+//! the HAL. On the host the example is empty.
+
+#![cfg_attr(target_os = "none", no_std, no_main)]
+
+#[cfg(target_os = "none")]
+mod bare_metal {
+    use core::fmt::{self, Write};
+    use core::pin::pin;
+    use cortex_m_rt::entry;
+    use panic_write::{set_action, set_reset_delay, PanicAction, PanicHandler};
+
+    /// The data register of UART0
+    const UART0_DR: *mut u32 = 0x4000_c000 as *mut u32;
+
+    struct Uart;
+
+    impl Write for Uart {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            for byte in s.bytes() {
+                // safe because the register only takes the byte to send
+                unsafe { UART0_DR.write_volatile(byte.into()) };
+            }
+            Ok(())
+        }
+    }
+
+    #[entry]
+    fn main() -> ! {
+        set_action(PanicAction::Reset);
+        // give the uart some time to send out the last bytes
+        set_reset_delay(100_000);
+
+        let handler = pin!(PanicHandler::new(Uart));
+        let _registration = handler.register();
+
+        panic!("boom");
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+fn main() {}
@@ -0,0 +1,30 @@
+//! Write panics to the console of QEMU using semihosting
+//!
+//! ```text
+//! cargo build --example semihosting --features semihosting --target thumbv7m-none-eabi
+//! qemu-system-arm -machine lm3s6965evb -nographic -semihosting-config enable=on,target=native \
+//!     -kernel target/thumbv7m-none-eabi/debug/examples/semihosting
+//! ```
+//!
+//! On the host the example is empty.
+
+#![cfg_attr(target_os = "none", no_std, no_main)]
+
+#[cfg(target_os = "none")]
+mod bare_metal {
+    use core::pin::pin;
+    use cortex_m_rt::entry;
+    use panic_write::writers::SemihostingWriter;
+    use panic_write::PanicHandler;
+
+    #[entry]
+    fn main() -> ! {
+        let handler = pin!(PanicHandler::new(SemihostingWriter::stdout()));
+        let _registration = handler.register();
+
+        panic!("boom");
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+fn main() {}
@@ -0,0 +1,40 @@
+//! A handler writing to a uart, the baseline for the code size of the `size` test
+//!
+//! ```text
+//! cargo build --release --example size_one --features size-test --target thumbv7em-none-eabihf
+//! ```
+//!
+//! The `size` test builds this and `size_three` and compares their `.text` sizes. On the host the
+//! example is empty.
+
+#![cfg_attr(target_os = "none", no_std, no_main)]
+
+#[cfg(target_os = "none")]
+mod bare_metal {
+    use core::fmt::{self, Write};
+    use core::pin::pin;
+    use cortex_m_rt::entry;
+    use panic_write::PanicHandler;
+
+    struct Uart0;
+
+    impl Write for Uart0 {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            for byte in s.bytes() {
+                // safe because the register only takes the byte to send
+                unsafe { (0x4000_c000 as *mut u32).write_volatile(byte.into()) };
+            }
+            Ok(())
+        }
+    }
+
+    #[entry]
+    fn main() -> ! {
+        let handler = pin!(PanicHandler::new(Uart0));
+        let _registration = handler.register();
+        panic!("boom {}", core::hint::black_box(42));
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+fn main() {}
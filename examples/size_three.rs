@@ -0,0 +1,53 @@
+//! Three handlers writing to three different uart types, see `size_one`
+//!
+//! ```text
+//! cargo build --release --example size_three --features size-test --target thumbv7em-none-eabihf
+//! ```
+//!
+//! On the host the example is empty.
+
+#![cfg_attr(target_os = "none", no_std, no_main)]
+
+#[cfg(target_os = "none")]
+mod bare_metal {
+    use core::fmt::{self, Write};
+    use core::pin::pin;
+    use cortex_m_rt::entry;
+    use panic_write::PanicHandler;
+
+    macro_rules! uart {
+        ($name:ident, $data:expr) => {
+            struct $name;
+
+            impl Write for $name {
+                fn write_str(&mut self, s: &str) -> fmt::Result {
+                    for byte in s.bytes() {
+                        // safe because the register only takes the byte to send
+                        unsafe { ($data as *mut u32).write_volatile(byte.into()) };
+                    }
+                    Ok(())
+                }
+            }
+        };
+    }
+
+    uart!(Uart0, 0x4000_c000);
+    uart!(Uart1, 0x4000_d000);
+    uart!(Uart2, 0x4000_e000);
+
+    #[entry]
+    fn main() -> ! {
+        let handler0 = pin!(PanicHandler::new(Uart0));
+        let handler1 = pin!(PanicHandler::new(Uart1));
+        let handler2 = pin!(PanicHandler::new(Uart2));
+        let _registrations = (
+            handler0.register(),
+            handler1.register(),
+            handler2.register(),
+        );
+        panic!("boom {}", core::hint::black_box(42));
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+fn main() {}
@@ -0,0 +1,399 @@
+#[cfg(feature = "semihosting")]
+use core::sync::atomic::AtomicI32;
+#[cfg(feature = "cortex-m")]
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::{AtomicPtr, AtomicU8, Ordering};
+
+/// What the panic handler does after the panic message has been written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PanicAction {
+    /// Busy loop forever, this is the default
+    #[default]
+    SpinLoop,
+    /// Put the core to sleep with `wfi` between wakeups
+    Wfi,
+    /// Put the core to sleep with `wfe` between wakeups
+    Wfe,
+    /// Trap with an undefined instruction so a debugger or fault handler can catch it
+    ///
+    /// With the `cortex-m` feature this uses `cortex_m::asm::udf`, on other arm and aarch64
+    /// targets a `udf` instruction is emitted directly. Other targets fall back to spinning.
+    Abort,
+    /// Reset the system after waiting for the delay set by [`set_reset_delay`], or the delay of
+    /// the handler, see [`PanicHandler::uart_reset`](crate::PanicHandler::uart_reset)
+    ///
+    /// ```ignore
+    /// #[entry]
+    /// fn main() -> ! {
+    ///     let serial = ...;
+    ///
+    ///     panic_write::set_action(PanicAction::Reset);
+    ///     // give the uart some time to send out the last bytes
+    ///     panic_write::set_reset_delay(100_000);
+    ///
+    ///     let handler = pin!(PanicHandler::new(serial));
+    ///     let _registration = handler.register();
+    ///
+    ///     loop {}
+    /// }
+    /// ```
+    #[cfg(feature = "cortex-m")]
+    Reset,
+    /// Halt at a breakpoint so an attached debugger can inspect the panic
+    ///
+    /// Without a debugger attached the breakpoint doesn't halt the core (or causes a fault), after
+    /// which the handler falls back to spinning.
+    #[cfg(feature = "cortex-m")]
+    Breakpoint,
+    /// Exit the emulator or debug session with the given status code through semihosting
+    ///
+    /// This lets test runs in QEMU terminate with a failing status on panic instead of hanging.
+    /// QEMU has to be started with semihosting enabled, without a debugger to handle the
+    /// semihosting call the core halts or faults. If the host lets the program continue, or when
+    /// not running on an arm core, the handler falls back to spinning.
+    ///
+    /// ```ignore
+    /// // qemu-system-arm -machine lm3s6965evb -semihosting -nographic -kernel app
+    /// #[entry]
+    /// fn main() -> ! {
+    ///     panic_write::set_action(PanicAction::SemihostExit(1));
+    ///
+    ///     let handler = pin!(PanicHandler::new(SemihostingWriter::stdout()));
+    ///     let _registration = handler.register();
+    ///
+    ///     panic!("this exits qemu with status 1");
+    /// }
+    /// ```
+    #[cfg(feature = "semihosting")]
+    SemihostExit(i32),
+}
+
+impl PanicAction {
+    /// Decode an action stored with [`to_u8`](PanicAction::to_u8) and
+    /// [`exit_code`](PanicAction::exit_code)
+    #[cfg_attr(not(feature = "semihosting"), allow(unused_variables))]
+    fn from_u8(value: u8, exit_code: i32) -> Self {
+        match value {
+            1 => PanicAction::Wfi,
+            2 => PanicAction::Wfe,
+            3 => PanicAction::Abort,
+            #[cfg(feature = "cortex-m")]
+            4 => PanicAction::Reset,
+            #[cfg(feature = "cortex-m")]
+            5 => PanicAction::Breakpoint,
+            #[cfg(feature = "semihosting")]
+            6 => PanicAction::SemihostExit(exit_code),
+            _ => PanicAction::SpinLoop,
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            PanicAction::SpinLoop => 0,
+            PanicAction::Wfi => 1,
+            PanicAction::Wfe => 2,
+            PanicAction::Abort => 3,
+            #[cfg(feature = "cortex-m")]
+            PanicAction::Reset => 4,
+            #[cfg(feature = "cortex-m")]
+            PanicAction::Breakpoint => 5,
+            #[cfg(feature = "semihosting")]
+            PanicAction::SemihostExit(_) => 6,
+        }
+    }
+
+    /// The status code that doesn't fit in [`to_u8`](PanicAction::to_u8), 0 for actions without
+    /// one
+    fn exit_code(self) -> i32 {
+        match self {
+            #[cfg(feature = "semihosting")]
+            PanicAction::SemihostExit(code) => code,
+            _ => 0,
+        }
+    }
+}
+
+/// An action in a static, the exit code is kept next to it
+struct StoredAction {
+    action: AtomicU8,
+    #[cfg(feature = "semihosting")]
+    exit_code: AtomicI32,
+}
+
+impl StoredAction {
+    const fn new(action: u8) -> Self {
+        StoredAction {
+            action: AtomicU8::new(action),
+            #[cfg(feature = "semihosting")]
+            exit_code: AtomicI32::new(0),
+        }
+    }
+
+    fn store(&self, action: PanicAction) {
+        self.store_parts(action.to_u8(), action.exit_code());
+    }
+
+    #[cfg_attr(not(feature = "semihosting"), allow(unused_variables))]
+    fn store_parts(&self, action: u8, exit_code: i32) {
+        // the code is stored first, so it's there once the action can be seen
+        #[cfg(feature = "semihosting")]
+        self.exit_code.store(exit_code, Ordering::Release);
+        self.action.store(action, Ordering::Release);
+    }
+
+    /// The stored action in its `u8` form and its exit code
+    fn load_parts(&self) -> (u8, i32) {
+        let action = self.action.load(Ordering::Acquire);
+        #[cfg(feature = "semihosting")]
+        let exit_code = self.exit_code.load(Ordering::Acquire);
+        #[cfg(not(feature = "semihosting"))]
+        let exit_code = 0;
+        (action, exit_code)
+    }
+}
+
+static PANIC_ACTION: StoredAction = StoredAction::new(0);
+
+/// Set the action to take after a panic has been written
+///
+/// This can be called at any time, including before a handler is registered.
+pub fn set_action(action: PanicAction) {
+    PANIC_ACTION.store(action);
+}
+
+pub(crate) fn action() -> PanicAction {
+    let (action, exit_code) = PANIC_ACTION.load_parts();
+    PanicAction::from_u8(action, exit_code)
+}
+
+/// The action chosen by a deciding hook that just ran, `NO_DECISION` if there is none
+static DECISION: StoredAction = StoredAction::new(NO_DECISION);
+const NO_DECISION: u8 = u8::MAX;
+
+/// Record the action chosen by a hook, for the handler that's running it
+pub(crate) fn decide(action: PanicAction) {
+    DECISION.store(action);
+}
+
+/// The action chosen by the hook that just ran, if it made a decision
+pub(crate) fn take_decision() -> Option<PanicAction> {
+    let (decision, exit_code) = DECISION.load_parts();
+    DECISION.store_parts(NO_DECISION, 0);
+    match decision {
+        NO_DECISION => None,
+        decision => Some(PanicAction::from_u8(decision, exit_code)),
+    }
+}
+
+#[cfg(feature = "cortex-m")]
+static RESET_DELAY: AtomicU32 = AtomicU32::new(0);
+
+/// Set the number of cycles to wait before resetting with [`PanicAction::Reset`]
+///
+/// The delay runs after the panic message is written and flushed, giving hardware FIFOs time to
+/// drain. Sending a byte over a UART takes 10 bit times, so draining a FIFO of `depth` bytes
+/// takes `depth * 10 * core_clock / baud_rate` cycles. For a 16 byte FIFO at 115200 baud on a
+/// 64MHz core that's `16 * 10 * 64_000_000 / 115_200`, or about 89_000 cycles. The default is 0.
+#[cfg(feature = "cortex-m")]
+pub fn set_reset_delay(cycles: u32) {
+    RESET_DELAY.store(cycles, Ordering::Release);
+}
+
+/// The function set by [`set_idle`], or null
+static IDLE: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Set a function to call in every iteration of the loop the core ends up in after a panic
+///
+/// This can feed a watchdog to keep the system in the halted state, or signal a supervisor.
+/// With [`PanicAction::Wfi`] and [`PanicAction::Wfe`] it's called after every wakeup.
+pub fn set_idle(idle: fn()) {
+    IDLE.store(idle as *mut (), Ordering::Release);
+}
+
+fn idle() {
+    let idle = IDLE.load(Ordering::Acquire);
+    if !idle.is_null() {
+        // safe because only `fn()` pointers are stored
+        let idle: fn() = unsafe { core::mem::transmute(idle) };
+        idle();
+    }
+}
+
+/// The function set by [`set_resume`], or null
+static RESUME: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Set a function to call instead of halting once the panic has been written
+///
+/// For setups that can recover from a panic, e.g. a supervisor that restarts the failed task, the
+/// function can jump back to a known good state with a context switch or `longjmp`. It replaces
+/// the post-panic action, which is only used while no resume function is set. Once it's called
+/// a new panic is handled like the first one again.
+pub fn set_resume(resume: fn() -> !) {
+    RESUME.store(resume as *mut (), Ordering::Release);
+}
+
+pub(crate) fn resume() -> Option<fn() -> !> {
+    let resume = RESUME.load(Ordering::Acquire);
+    // safe because only `fn() -> !` pointers are stored
+    (!resume.is_null()).then(|| unsafe { core::mem::transmute::<*mut (), fn() -> !>(resume) })
+}
+
+/// Reset everything set in this module to the defaults
+#[cfg(any(test, feature = "simulate"))]
+pub(crate) fn reset() {
+    PANIC_ACTION.store(PanicAction::SpinLoop);
+    DECISION.store_parts(NO_DECISION, 0);
+    #[cfg(feature = "cortex-m")]
+    RESET_DELAY.store(0, Ordering::Release);
+    IDLE.store(core::ptr::null_mut(), Ordering::Release);
+    RESUME.store(core::ptr::null_mut(), Ordering::Release);
+}
+
+/// The post-panic action chosen for a panic, with the settings of the handler that chose it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Outcome {
+    pub(crate) action: PanicAction,
+    /// Replaces the global reset delay for [`PanicAction::Reset`]
+    #[cfg(feature = "cortex-m")]
+    pub(crate) reset_delay: Option<u32>,
+}
+
+impl From<PanicAction> for Outcome {
+    fn from(action: PanicAction) -> Self {
+        Outcome {
+            action,
+            #[cfg(feature = "cortex-m")]
+            reset_delay: None,
+        }
+    }
+}
+
+/// Perform the post-panic action, never returns
+pub(crate) fn halt(outcome: Outcome) -> ! {
+    match outcome.action {
+        PanicAction::SpinLoop => {}
+        PanicAction::Wfi => loop {
+            wfi();
+            idle();
+        },
+        PanicAction::Wfe => loop {
+            wfe();
+            idle();
+        },
+        PanicAction::Abort => udf(),
+        #[cfg(feature = "cortex-m")]
+        PanicAction::Reset => {
+            let delay = outcome.reset_delay;
+            cortex_m::asm::delay(delay.unwrap_or_else(|| RESET_DELAY.load(Ordering::Acquire)));
+            cortex_m::peripheral::SCB::sys_reset()
+        }
+        #[cfg(feature = "cortex-m")]
+        PanicAction::Breakpoint => cortex_m::asm::bkpt(),
+        #[cfg(feature = "semihosting")]
+        PanicAction::SemihostExit(code) => semihost_exit(code),
+    }
+    loop {
+        idle();
+        core::hint::spin_loop();
+    }
+}
+
+/// Exit with `SYS_EXIT_EXTENDED`, which unlike `debug::exit` passes on the status code
+#[cfg(all(feature = "semihosting", target_arch = "arm"))]
+fn semihost_exit(code: i32) {
+    const SYS_EXIT_EXTENDED: usize = 0x20;
+    const ADP_STOPPED_APPLICATION_EXIT: usize = 0x20026;
+    unsafe {
+        cortex_m_semihosting::syscall(
+            SYS_EXIT_EXTENDED,
+            &[ADP_STOPPED_APPLICATION_EXIT, code as usize],
+        );
+    }
+}
+
+// semihosting calls are only implemented for arm
+#[cfg(all(feature = "semihosting", not(target_arch = "arm")))]
+fn semihost_exit(_code: i32) {}
+
+#[cfg(any(target_arch = "arm", target_arch = "aarch64"))]
+fn wfi() {
+    unsafe { core::arch::asm!("wfi", options(nomem, nostack, preserves_flags)) }
+}
+
+#[cfg(any(target_arch = "arm", target_arch = "aarch64"))]
+fn wfe() {
+    unsafe { core::arch::asm!("wfe", options(nomem, nostack, preserves_flags)) }
+}
+
+#[cfg(feature = "cortex-m")]
+fn udf() {
+    cortex_m::asm::udf()
+}
+
+#[cfg(all(
+    not(feature = "cortex-m"),
+    any(target_arch = "arm", target_arch = "aarch64")
+))]
+fn udf() {
+    unsafe { core::arch::asm!("udf #0", options(nomem, nostack)) }
+}
+
+// other targets have no sleep or trap instruction we can rely on, fall back to spinning
+#[cfg(not(any(target_arch = "arm", target_arch = "aarch64")))]
+fn wfi() {
+    core::hint::spin_loop();
+}
+
+#[cfg(not(any(target_arch = "arm", target_arch = "aarch64")))]
+fn wfe() {
+    core::hint::spin_loop();
+}
+
+#[cfg(not(any(feature = "cortex-m", target_arch = "arm", target_arch = "aarch64")))]
+fn udf() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::globals;
+    use core::sync::atomic::AtomicUsize;
+
+    static IDLE_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    fn count_idle() {
+        IDLE_CALLS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn idle_calls_the_function_that_was_set() {
+        let _globals = globals();
+        set_idle(count_idle);
+        idle();
+        idle();
+        assert_eq!(IDLE_CALLS.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "semihosting")]
+    fn deciding_an_exit_keeps_the_global_exit_code() {
+        let _globals = globals();
+        set_action(PanicAction::SemihostExit(1));
+        decide(PanicAction::SemihostExit(2));
+        assert_eq!(take_decision(), Some(PanicAction::SemihostExit(2)));
+        assert_eq!(action(), PanicAction::SemihostExit(1));
+        assert_eq!(take_decision(), None);
+        set_action(PanicAction::SpinLoop);
+    }
+
+    #[test]
+    fn resume_returns_the_function_that_was_set() {
+        fn restart_task() -> ! {
+            std::panic::panic_any("resumed")
+        }
+
+        let _globals = globals();
+        set_resume(restart_task);
+        let resumed = std::panic::catch_unwind(|| resume().unwrap()());
+        assert_eq!(resumed.unwrap_err().downcast_ref(), Some(&"resumed"));
+    }
+}
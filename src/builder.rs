@@ -0,0 +1,484 @@
+use crate::writers::CappingWriter;
+#[cfg(feature = "nb")]
+use crate::PollFlush;
+use crate::{default_hook, Options, PanicAction, PanicHandler, PanicInfo, PanicWriter};
+use core::fmt::Write;
+
+/// Builder for a [`PanicHandler`] with optional features
+///
+/// ```ignore
+/// use panic_write::{PanicAction, PanicHandlerBuilder};
+///
+/// let handler = PanicHandlerBuilder::new()
+///     .prefix("PANIC: ")
+///     .action(PanicAction::Wfi)
+///     .build(serial);
+/// ```
+///
+/// Any option that isn't set behaves the same as a handler created with [`PanicHandler::new`].
+pub struct PanicHandlerBuilder<W: Write, F: FnMut(&mut W, &PanicInfo) = fn(&mut W, &PanicInfo)> {
+    hook: F,
+    options: Options<W>,
+}
+
+impl<W: Write> PanicHandlerBuilder<W> {
+    pub fn new() -> Self {
+        PanicHandlerBuilder {
+            hook: default_hook::<W>,
+            options: Options::new(),
+        }
+    }
+}
+
+impl<W: Write> Default for PanicHandlerBuilder<W> {
+    fn default() -> Self {
+        PanicHandlerBuilder::new()
+    }
+}
+
+impl<W: Write, F: FnMut(&mut W, &PanicInfo)> PanicHandlerBuilder<W, F> {
+    /// Use a custom hook to write the panic message
+    pub fn hook<G: FnMut(&mut W, &PanicInfo)>(self, hook: G) -> PanicHandlerBuilder<W, G> {
+        PanicHandlerBuilder {
+            hook,
+            options: self.options,
+        }
+    }
+
+    /// Run a side effect before the panic message is written, see
+    /// [`PanicHandler::new_with_side_effect`]
+    pub fn side_effect(mut self, side_effect: fn(&PanicInfo)) -> Self {
+        self.options.common.side_effect = Some(side_effect);
+        self
+    }
+
+    /// Disable the watchdog as the very first step when a panic happens, before the side effect
+    /// and before anything is written
+    ///
+    /// This keeps the watchdog from resetting the device while the halted state is inspected
+    /// with a debugger. To keep the device alive while writing instead, use a
+    /// [`FeedingWriter`](crate::writers::FeedingWriter).
+    pub fn disable_watchdog(mut self, disable_watchdog: fn()) -> Self {
+        self.options.common.disable_watchdog = Some(disable_watchdog);
+        self
+    }
+
+    /// Flush the writer after the panic message is written, see [`Flush`](crate::Flush)
+    pub fn flush(mut self, flush: fn(&mut W)) -> Self {
+        self.options.flush = Some(flush);
+        self
+    }
+
+    /// Wait for a non-blocking flush to complete before the post-panic action, e.g. a DMA
+    /// transfer of the panic message
+    ///
+    /// Once all handlers wrote the panic, `flush` is polled until it returns anything but
+    /// `Err(nb::Error::WouldBlock)`, right before the handler halts or resets. After `max_polls`
+    /// polls the handler gives up and continues anyway, so a transfer that never completes doesn't
+    /// keep the device from resetting. The error of a failed flush is ignored.
+    #[cfg(feature = "nb")]
+    pub fn poll_flush<E>(mut self, flush: fn(&mut W) -> nb::Result<(), E>, max_polls: u32) -> Self {
+        self.options.poll_flush = Some(PollFlush::new(flush, max_polls));
+        self
+    }
+
+    /// Set the post-panic action for this handler, overriding the one set by
+    /// [`set_action`](crate::set_action)
+    pub fn action(mut self, action: PanicAction) -> Self {
+        self.options.common.action = Some(action);
+        self
+    }
+
+    /// Write a fixed string before the panic message
+    pub fn prefix(mut self, prefix: &'static str) -> Self {
+        self.options.common.prefix = Some(prefix);
+        self
+    }
+
+    /// Write the firmware identity before the panic message, after the prefix and before the
+    /// timestamp
+    ///
+    /// Intended for the version and build of the firmware, e.g.
+    /// `concat!("firmware ", env!("CARGO_PKG_VERSION"), " ", env!("GIT_HASH"), "\n")`, so crash
+    /// reports from the field can be matched to the build that produced them.
+    pub fn banner(mut self, banner: &'static str) -> Self {
+        self.options.common.banner = Some(banner);
+        self
+    }
+
+    /// Surround all output of the handler with sentinels written by `start` and `end`, so a host
+    /// can find the panic in a binary stream
+    ///
+    /// This is the binary counterpart of the [`prefix`](PanicHandlerBuilder::prefix) and
+    /// [`suffix`](PanicHandlerBuilder::suffix). `core::fmt::Write` only carries utf-8, so the
+    /// functions write raw bytes through the writer's own api:
+    ///
+    /// ```ignore
+    /// const START: &[u8] = &[0xde, 0xad, 0xbe, 0xef];
+    /// const END: &[u8] = &[0xef, 0xbe, 0xad, 0xde];
+    ///
+    /// let handler = PanicHandlerBuilder::new()
+    ///     .framing(
+    ///         |uart: &mut EioWriter<_>| drop(uart.inner().write_all(START)),
+    ///         |uart: &mut EioWriter<_>| drop(uart.inner().write_all(END)),
+    ///     )
+    ///     .build(uart);
+    /// ```
+    ///
+    /// The end sentinel is written after the suffix and before the writer is finished or flushed.
+    pub fn framing(mut self, start: fn(&mut W), end: fn(&mut W)) -> Self {
+        self.options.framing = Some((start, end));
+        self
+    }
+
+    /// Write a fixed string after the panic message
+    pub fn suffix(mut self, suffix: &'static str) -> Self {
+        self.options.common.suffix = Some(suffix);
+        self
+    }
+
+    /// Write extra context after the panic message, like the uptime or heap usage, without
+    /// replacing the hook
+    pub fn context(mut self, context: fn(&mut W)) -> Self {
+        self.options.context = Some(context);
+        self
+    }
+
+    /// Write the id of the device after the panic message and context, to tell apart crash
+    /// logs from identical boards
+    ///
+    /// Unlike the [`banner`](PanicHandlerBuilder::banner) the id is written by a function, so it
+    /// can be read at runtime, e.g. from a factory programmed flash region.
+    pub fn device_id(mut self, device_id: fn(&mut W)) -> Self {
+        self.options.device_id = Some(device_id);
+        self
+    }
+
+    /// End the hook output with `"\r\n"` so consecutive messages don't run together
+    ///
+    /// Use [`line_ending`](PanicHandlerBuilder::line_ending) for a different sequence.
+    pub fn trailing_newline(mut self, enabled: bool) -> Self {
+        self.options.common.line_ending = if enabled { Some("\r\n") } else { None };
+        self
+    }
+
+    /// Add a `key=value` field after the panic message, e.g. `task=net`, for log pipelines that
+    /// parse structured fields
+    ///
+    /// Fields are written as ` <key>=` followed by whatever `value` writes, in the order they were
+    /// added and after the device id. Up to [`MAX_FIELDS`](crate::MAX_FIELDS) fields can be added.
+    ///
+    /// # Panics
+    ///
+    /// Panics if more than [`MAX_FIELDS`](crate::MAX_FIELDS) fields are added.
+    pub fn field(mut self, key: &'static str, value: fn(&mut W)) -> Self {
+        let slot = self.options.fields.iter_mut().find(|field| field.is_none());
+        *slot.expect("too many fields") = Some((key, value));
+        self
+    }
+
+    /// End the hook output with a custom line ending, e.g. `"\n"`
+    pub fn line_ending(mut self, line_ending: &'static str) -> Self {
+        self.options.common.line_ending = Some(line_ending);
+        self
+    }
+
+    /// Write a timestamp read from `clock` before the panic message, see
+    /// [`PanicHandler::new_with_clock`]
+    pub fn clock(mut self, clock: fn() -> u64) -> Self {
+        self.options.common.clock = Some(clock);
+        self
+    }
+
+    /// Start the output with a line containing the panic count and the uptime,
+    /// `#<panic_count> @<uptime>`, to tell crashes apart in soak tests
+    ///
+    /// The count is [`panic_count`](crate::panic_count) including the current panic, the uptime is
+    /// whatever `uptime` returns, e.g. ticks since boot.
+    pub fn stats_header(mut self, uptime: fn() -> u64) -> Self {
+        self.options.common.stats_header = Some(uptime);
+        self
+    }
+
+    /// Write only the location of the panic as `<file>:<line>` to a second writer
+    ///
+    /// Unlike [`TeeWriter`](crate::writers::TeeWriter) the two writers get different output, the
+    /// summary can be a small always available channel next to a slow writer for the full
+    /// message. The summary is written first.
+    pub fn summary_sink(mut self, summary: &'static mut dyn Write) -> Self {
+        self.options.common.summary = Some(summary);
+        self
+    }
+
+    /// Write a short line before the full panic message, see [`PanicHandler::new_with_headline`]
+    pub fn headline(mut self, headline: fn(&mut W, &PanicInfo)) -> Self {
+        self.options.headline = Some(headline);
+        self
+    }
+
+    #[must_use = "the panic handler must be kept in scope"]
+    pub fn build(self, mut writer: W) -> PanicHandler<W, F> {
+        if let Some((max_bytes, apply)) = self.options.max_bytes {
+            apply(&mut writer, max_bytes);
+        }
+        let mut handler = PanicHandler::new_with_hook(writer, self.hook);
+        handler.options = self.options;
+        handler
+    }
+}
+
+impl<W: PanicWriter, F: FnMut(&mut W, &PanicInfo)> PanicHandlerBuilder<W, F> {
+    /// Call the [`PanicWriter`] methods of the writer, see [`PanicHandler::new_panic_writer`]
+    pub fn panic_writer(mut self) -> Self {
+        self.options.finish = Some(<W as PanicWriter>::finish);
+        self.options.flush = Some(<W as PanicWriter>::flush);
+        self
+    }
+}
+
+impl<W: Write, F: FnMut(&mut CappingWriter<W>, &PanicInfo)>
+    PanicHandlerBuilder<CappingWriter<W>, F>
+{
+    /// Stop writing the panic message after `max_bytes`, see [`CappingWriter`]
+    ///
+    /// This overrides the limit the writer passed to [`build`](PanicHandlerBuilder::build) was
+    /// created with.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.options.max_bytes = Some((max_bytes, CappingWriter::set_max_bytes));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulate_panic_with;
+    use crate::tests::globals;
+
+    #[test]
+    fn line_ending_comes_before_the_suffix() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandlerBuilder::new()
+            .trailing_newline(true)
+            .suffix("--")
+            .build(String::new()));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(*registration, "panicked at :\nboom\r\n--");
+    }
+
+    #[test]
+    fn custom_line_ending() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandlerBuilder::new()
+            .line_ending("\n")
+            .build(String::new()));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(*registration, "panicked at :\nboom\n");
+    }
+
+    #[test]
+    fn banner_comes_between_prefix_and_timestamp() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandlerBuilder::new()
+            .prefix("!! ")
+            .banner("firmware 1.0\n")
+            .clock(|| 7)
+            .build(String::new()));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(*registration, "!! firmware 1.0\n[7] panicked at :\nboom");
+    }
+
+    #[test]
+    fn framing_surrounds_everything_but_the_flush() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandlerBuilder::new()
+            .framing(|out: &mut String| out.push('\x02'), |out| out.push('\x03'))
+            .prefix("!! ")
+            .suffix("--")
+            .flush(|out| out.push_str(" flushed"))
+            .build(String::new()));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(*registration, "\x02!! panicked at :\nboom--\x03 flushed");
+    }
+
+    #[test]
+    fn summary_only_gets_the_location() {
+        use std::sync::Mutex;
+
+        static SUMMARY: Mutex<String> = Mutex::new(String::new());
+
+        /// Second channel that collects its output in `SUMMARY`
+        struct Summary;
+
+        impl Write for Summary {
+            fn write_str(&mut self, s: &str) -> core::fmt::Result {
+                SUMMARY.lock().unwrap().push_str(s);
+                Ok(())
+            }
+        }
+
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandlerBuilder::new()
+            .summary_sink(Box::leak(Box::new(Summary)))
+            .build(String::new()));
+        let registration = handler.register();
+        let location = core::panic::Location::caller();
+        simulate_panic_with(format_args!("boom"), Some(location));
+        assert_eq!(
+            *SUMMARY.lock().unwrap(),
+            format!("{}:{}", location.file(), location.line())
+        );
+        assert_eq!(*registration, format!("panicked at {}:\nboom", location));
+    }
+
+    #[test]
+    fn context_comes_after_the_message() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandlerBuilder::new()
+            .context(|out: &mut String| out.push_str("\nuptime: 12s"))
+            .line_ending("\n")
+            .build(String::new()));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(*registration, "panicked at :\nboom\nuptime: 12s\n");
+    }
+
+    #[test]
+    fn device_id_comes_after_the_context() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandlerBuilder::new()
+            .context(|out: &mut String| out.push_str("\nuptime: 12s"))
+            .device_id(|out: &mut String| out.push_str("\nid: 0042"))
+            .build(String::new()));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(*registration, "panicked at :\nboom\nuptime: 12s\nid: 0042");
+    }
+
+    #[test]
+    fn fields_come_after_the_device_id_in_order() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandlerBuilder::new()
+            .device_id(|out: &mut String| out.push_str("\nid: 0042"))
+            .field("task", |out: &mut String| out.push_str("net"))
+            .field("heap", |out: &mut String| out.push_str("12k"))
+            .build(String::new()));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(
+            *registration,
+            "panicked at :\nboom\nid: 0042 task=net heap=12k"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "too many fields")]
+    fn too_many_fields() {
+        let mut builder = PanicHandlerBuilder::<String, _>::new();
+        for _ in 0..=crate::MAX_FIELDS {
+            builder = builder.field("key", |_| {});
+        }
+    }
+
+    #[test]
+    fn disable_watchdog_runs_before_the_side_effect_and_the_first_write() {
+        use std::sync::Mutex;
+
+        static CALLS: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+
+        /// Records its writes in `CALLS`
+        struct Recorder;
+
+        impl Write for Recorder {
+            fn write_str(&mut self, _s: &str) -> core::fmt::Result {
+                CALLS.lock().unwrap().push("write_str");
+                Ok(())
+            }
+        }
+
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandlerBuilder::new()
+            .side_effect(|_| CALLS.lock().unwrap().push("side_effect"))
+            .disable_watchdog(|| CALLS.lock().unwrap().push("disable_watchdog"))
+            .build(Recorder));
+        let _registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(
+            CALLS.lock().unwrap()[..3],
+            ["disable_watchdog", "side_effect", "write_str"]
+        );
+    }
+
+    /// A uart whose DMA transfer completes after a number of polls
+    #[cfg(feature = "nb")]
+    struct DmaUart {
+        written: String,
+        polls: u32,
+        complete_after: u32,
+        /// What was written when the flush was first polled
+        written_at_first_poll: Option<String>,
+    }
+
+    #[cfg(feature = "nb")]
+    impl DmaUart {
+        fn new(complete_after: u32) -> Self {
+            DmaUart {
+                written: String::new(),
+                polls: 0,
+                complete_after,
+                written_at_first_poll: None,
+            }
+        }
+
+        fn poll_flush(&mut self) -> nb::Result<(), ()> {
+            if self.polls == 0 {
+                self.written_at_first_poll = Some(self.written.clone());
+            }
+            self.polls += 1;
+            if self.polls < self.complete_after {
+                return Err(nb::Error::WouldBlock);
+            }
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "nb")]
+    impl Write for DmaUart {
+        fn write_str(&mut self, s: &str) -> core::fmt::Result {
+            self.written.push_str(s);
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "nb")]
+    fn poll_flush_waits_for_the_flush_to_complete() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandlerBuilder::new()
+            .poll_flush(DmaUart::poll_flush, 10)
+            .build(DmaUart::new(3)));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(registration.polls, 3);
+        assert_eq!(
+            registration.written_at_first_poll.as_deref(),
+            Some("panicked at :\nboom")
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "nb")]
+    fn poll_flush_gives_up_after_max_polls() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandlerBuilder::new()
+            .poll_flush(DmaUart::poll_flush, 5)
+            .build(DmaUart::new(u32::MAX)));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(registration.polls, 5);
+    }
+}
@@ -0,0 +1,87 @@
+use crate::PanicInfo;
+use core::fmt::Write;
+
+/// Hook that logs the panic through `defmt` instead of the handler's writer
+///
+/// Since the message goes out over the `defmt` transport the writer isn't used at all, a writer
+/// that discards everything can be used as the handler's writer.
+pub fn defmt_hook<W: Write>(_out: &mut W, info: &PanicInfo) {
+    let message = defmt::Display2Format(&info.message());
+    match info.location() {
+        Some(location) => defmt::error!(
+            "panicked at {}:{}: {}",
+            location.file(),
+            location.line(),
+            message
+        ),
+        None => defmt::error!("panicked: {}", message),
+    }
+}
+
+/// Hook that logs the panic through `defmt` and also writes it as plain text to the handler's
+/// writer
+///
+/// For setups where `defmt` goes out over e.g. RTT while a plain copy goes to a uart, for whoever
+/// doesn't have the decoder at hand. The plain text is written like the default hook.
+pub fn defmt_tee_hook<W: Write>(out: &mut W, info: &PanicInfo) {
+    defmt_hook(out, info);
+    crate::default_hook(out, info);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulate_panic_with;
+    use crate::tests::globals;
+    use crate::PanicHandler;
+    use std::sync::Mutex;
+
+    /// The bytes logged through `defmt` by the tests
+    static LOGGED: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+    #[defmt::global_logger]
+    struct Logger;
+
+    defmt::timestamp!("");
+
+    unsafe impl defmt::Logger for Logger {
+        fn acquire() {}
+
+        unsafe fn flush() {}
+
+        unsafe fn release() {}
+
+        unsafe fn write(bytes: &[u8]) {
+            LOGGED.lock().unwrap().extend_from_slice(bytes);
+        }
+    }
+
+    #[test]
+    fn logs_the_panic_through_defmt() {
+        let _globals = globals();
+        let location = core::panic::Location::caller();
+        let handler = core::pin::pin!(PanicHandler::new_with_hook(String::new(), defmt_hook));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom {}", 42), Some(location));
+
+        // the raw encoding keeps string arguments as they are
+        let logged = std::mem::take(&mut *LOGGED.lock().unwrap());
+        let contains = |needle: &[u8]| logged.windows(needle.len()).any(|w| w == needle);
+        assert!(contains(location.file().as_bytes()));
+        assert!(contains(b"boom 42"));
+        // and nothing was written to the writer
+        assert_eq!(*registration, "");
+    }
+
+    #[test]
+    fn tee_hook_also_writes_plain_text() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_hook(String::new(), defmt_tee_hook));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("tee {}", 7), None);
+
+        let logged = std::mem::take(&mut *LOGGED.lock().unwrap());
+        assert!(logged.windows(5).any(|w| w == b"tee 7"));
+        assert_eq!(*registration, "panicked at :\ntee 7");
+    }
+}
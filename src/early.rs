@@ -0,0 +1,86 @@
+//! Writer for panics before a panic handler is registered
+
+use crate::registry;
+use crate::PanicInfo;
+use core::cell::UnsafeCell;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+struct EarlyWriter(UnsafeCell<Option<&'static mut dyn Write>>);
+
+// only accessed while holding the registry lock or during a panic
+unsafe impl Sync for EarlyWriter {}
+
+static EARLY_WRITER: EarlyWriter = EarlyWriter(UnsafeCell::new(None));
+/// Whether `EARLY_WRITER` is set, cleared while it's being changed
+static EARLY_WRITER_SET: AtomicBool = AtomicBool::new(false);
+
+/// Set a writer for panics that happen while no panic handler is registered
+///
+/// This covers the window during startup before the real writer is set up, a minimal writer
+/// that doesn't need any setup, like a [`SemihostingWriter`](crate::writers::SemihostingWriter)
+/// or a writer poking a UART register directly, can catch those panics. The panic is written
+/// with the default hook.
+pub fn set_early_writer(writer: &'static mut dyn Write) {
+    registry::locked(move || {
+        // a panic while the writer is being replaced skips the early writer
+        EARLY_WRITER_SET.store(false, Ordering::Release);
+        unsafe { *EARLY_WRITER.0.get() = Some(writer) };
+        EARLY_WRITER_SET.store(true, Ordering::Release);
+    })
+}
+
+/// Unset the early writer
+#[cfg(any(test, feature = "simulate"))]
+pub(crate) fn clear() {
+    registry::locked(|| {
+        EARLY_WRITER_SET.store(false, Ordering::Release);
+        unsafe { *EARLY_WRITER.0.get() = None };
+    })
+}
+
+/// Write the panic to the early writer, if one is set
+pub(crate) fn write_panic(info: &PanicInfo) {
+    if !EARLY_WRITER_SET.load(Ordering::Acquire) {
+        return;
+    }
+    if let Some(writer) = unsafe { &mut *EARLY_WRITER.0.get() } {
+        crate::default_hook(writer, info);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::globals;
+    use crate::{simulate_panic_with, PanicHandler};
+    use std::sync::Mutex;
+
+    static EARLY: Mutex<String> = Mutex::new(String::new());
+
+    /// Early writer that collects its output in `EARLY`, it stays set for all following tests
+    struct Early;
+
+    impl Write for Early {
+        fn write_str(&mut self, s: &str) -> core::fmt::Result {
+            EARLY.lock().unwrap().push_str(s);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn early_writer_gets_panics_without_a_handler() {
+        let _globals = globals();
+        set_early_writer(Box::leak(Box::new(Early)));
+        EARLY.lock().unwrap().clear();
+        simulate_panic_with(format_args!("early"), None);
+        assert_eq!(*EARLY.lock().unwrap(), "panicked at :\nearly");
+
+        EARLY.lock().unwrap().clear();
+        let handler = core::pin::pin!(PanicHandler::new(String::new()));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("late"), None);
+        assert_eq!(*EARLY.lock().unwrap(), "");
+        assert_eq!(*registration, "panicked at :\nlate");
+    }
+}
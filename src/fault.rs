@@ -0,0 +1,110 @@
+//! Reporting hard faults through the registered panic handlers
+
+use core::fmt::{Display, Formatter, Result};
+use cortex_m_rt::ExceptionFrame;
+
+/// Report a hard fault through the registered panic handlers, for use in the `HardFault`
+/// exception handler of `cortex-m-rt`
+///
+/// The fault is turned into a panic, so it's written with the same writer, hook and post-panic
+/// action as a panic, see [`handle_panic`](crate::handle_panic). The message contains the stacked
+/// program counter and, except on armv6-m which doesn't have them, the fault status and address
+/// registers, e.g.
+/// `hard fault at 0x08000412, HFSR=0x40000000 CFSR=0x00008200 MMFAR=0x00000000 BFAR=0x20010000`.
+/// The handler has to be wired up by the application:
+///
+/// ```ignore
+/// use cortex_m_rt::{exception, ExceptionFrame};
+///
+/// #[exception]
+/// unsafe fn HardFault(frame: &ExceptionFrame) -> ! {
+///     panic_write::report_hard_fault(frame)
+/// }
+/// ```
+pub fn report_hard_fault(frame: &ExceptionFrame) -> ! {
+    panic!(
+        "{}",
+        HardFault {
+            pc: frame.pc(),
+            status: fault_status(),
+        }
+    )
+}
+
+/// Description of a hard fault, written as the panic message
+struct HardFault {
+    pc: u32,
+    status: Option<FaultStatus>,
+}
+
+/// The fault status and address registers
+struct FaultStatus {
+    hfsr: u32,
+    cfsr: u32,
+    mmfar: u32,
+    bfar: u32,
+}
+
+impl Display for HardFault {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "hard fault at {:#010x}", self.pc)?;
+        if let Some(status) = &self.status {
+            write!(
+                f,
+                ", HFSR={:#010x} CFSR={:#010x} MMFAR={:#010x} BFAR={:#010x}",
+                status.hfsr, status.cfsr, status.mmfar, status.bfar
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(target_arch = "arm", has_fault_status))]
+fn fault_status() -> Option<FaultStatus> {
+    // safe because the registers are only read
+    let scb = unsafe { &*cortex_m::peripheral::SCB::PTR };
+    Some(FaultStatus {
+        hfsr: scb.hfsr.read(),
+        cfsr: scb.cfsr.read(),
+        mmfar: scb.mmfar.read(),
+        bfar: scb.bfar.read(),
+    })
+}
+
+#[cfg(not(all(target_arch = "arm", has_fault_status)))]
+fn fault_status() -> Option<FaultStatus> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_the_fault_status() {
+        let fault = HardFault {
+            pc: 0x0800_0412,
+            status: Some(FaultStatus {
+                hfsr: 0x4000_0000,
+                cfsr: 0x8200,
+                mmfar: 0,
+                bfar: 0x2001_0000,
+            }),
+        };
+        assert_eq!(
+            fault.to_string(),
+            "hard fault at 0x08000412, HFSR=0x40000000 CFSR=0x00008200 MMFAR=0x00000000 \
+            BFAR=0x20010000"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "hard fault at 0x08000412")]
+    fn panics_with_the_stacked_pc() {
+        // r0-r3, r12, lr, pc and xpsr, in the order they're stacked
+        let registers: [u32; 8] = [0, 0, 0, 0, 0, 0x0800_0437, 0x0800_0412, 0x0100_0000];
+        // safe because the frame is `repr(C)` with the stacked registers as its fields
+        let frame: ExceptionFrame = unsafe { core::mem::transmute(registers) };
+        report_hard_fault(&frame);
+    }
+}
@@ -0,0 +1,605 @@
+//! Hooks for writing the panic message in alternative formats
+//!
+//! All hooks can be passed to [`PanicHandler::new_with_hook`](crate::PanicHandler::new_with_hook).
+//! The helpers like [`hex_dump`] can be used to write custom hooks.
+//!
+//! The hooks always write something, an empty panic message is written as `panic (no info)`.
+
+use crate::PanicInfo;
+use core::fmt::Write;
+
+/// Hook that writes the panic as `PANIC <file>:<line>:<col> <message>`
+///
+/// When the panic has no location, `<unknown>` is written in its place.
+pub fn location_hook<W: Write>(out: &mut W, info: &PanicInfo) {
+    let _ = out.write_str("PANIC ");
+    let _ = match info.location() {
+        Some(location) => write!(
+            out,
+            "{}:{}:{}",
+            location.file(),
+            location.line(),
+            location.column()
+        ),
+        None => out.write_str("<unknown>"),
+    };
+    let _ = out.write_str(" ");
+    write_message(out, info);
+}
+
+/// Hook that writes the panic as `<file>:<line>:<col> <message>`, with only the file name of the
+/// path
+///
+/// Full paths take up bytes on slow links and can leak the directory layout of the build machine
+/// into field logs, this keeps only what follows the last `/` or `\`. When the panic has no
+/// location, `<unknown>` is written in its place.
+pub fn basename_hook<W: Write>(out: &mut W, info: &PanicInfo) {
+    let _ = match info.location() {
+        Some(location) => write!(
+            out,
+            "{}:{}:{} ",
+            basename(location.file()),
+            location.line(),
+            location.column()
+        ),
+        None => out.write_str("<unknown> "),
+    };
+    write_message(out, info);
+}
+
+/// The last component of a unix or windows path
+fn basename(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+/// Hook that only writes the location as `PANIC@<file>:<line>`, intended as a headline for
+/// [`PanicHandler::new_with_headline`](crate::PanicHandler::new_with_headline)
+///
+/// When the panic has no location, `<unknown>` is written in its place.
+pub fn headline_hook<W: Write>(out: &mut W, info: &PanicInfo) {
+    let _ = match info.location() {
+        Some(location) => write!(out, "PANIC@{}:{}", location.file(), location.line()),
+        None => out.write_str("PANIC@<unknown>"),
+    };
+}
+
+/// Hook that writes the panic as `panicked at '<message>', <file>:<line>:<col>`
+///
+/// The output is put together from the fields of the panic info instead of its `Display`
+/// implementation, which can change between toolchains, so this is the recommended hook when the
+/// exact output matters, e.g. for golden tests. When the panic has no location the output ends
+/// after the message: `panicked at '<message>'`.
+pub fn stable_hook<W: Write>(out: &mut W, info: &PanicInfo) {
+    let _ = out.write_str("panicked at '");
+    write_message(out, info);
+    let _ = out.write_str("'");
+    if let Some(location) = info.location() {
+        let _ = write!(
+            out,
+            ", {}:{}:{}",
+            location.file(),
+            location.line(),
+            location.column()
+        );
+    }
+}
+
+/// Hook that writes the panic as a single line json object
+///
+/// The output has the form `{"level":"panic","file":"src/x.rs","line":42,"msg":"..."}`, with
+/// `file` and `line` set to `null` when the panic has no location.
+pub fn json_hook<W: Write>(out: &mut W, info: &PanicInfo) {
+    let _ = out.write_str("{\"level\":\"panic\",");
+    let _ = match info.location() {
+        Some(location) => {
+            let _ = out.write_str("\"file\":\"");
+            let _ = JsonEscape(out).write_str(location.file());
+            write!(out, "\",\"line\":{},", location.line())
+        }
+        None => out.write_str("\"file\":null,\"line\":null,"),
+    };
+    let _ = out.write_str("\"msg\":\"");
+    write_message(&mut JsonEscape(out), info);
+    let _ = out.write_str("\"}");
+}
+
+/// Hook that writes the panic as two labeled lines, `MSG: <message>` and `AT: <file>:<line>`
+///
+/// Unlike the `Display` output of the panic info the format doesn't depend on the toolchain. When
+/// the panic has no location, `<none>` is written in its place.
+pub fn labeled_hook<W: Write>(out: &mut W, info: &PanicInfo) {
+    let _ = out.write_str("MSG: ");
+    write_message(out, info);
+    let _ = match info.location() {
+        Some(location) => write!(out, "\nAT: {}:{}", location.file(), location.line()),
+        None => out.write_str("\nAT: <none>"),
+    };
+}
+
+/// Hook that writes the active exception before the panic, to tell if the panic happened in an
+/// interrupt handler
+///
+/// The panic is prefixed with `[thread mode] `, `[exception HardFault] ` or `[irq <n>] ` and
+/// then written like the default hook. When not running on a Cortex-M core there is no prefix.
+#[cfg(feature = "cortex-m")]
+pub fn context_hook<W: Write>(out: &mut W, info: &PanicInfo) {
+    #[cfg(target_arch = "arm")]
+    write_context(out, cortex_m::peripheral::SCB::vect_active());
+    crate::default_hook(out, info);
+}
+
+#[cfg(all(feature = "cortex-m", target_arch = "arm"))]
+fn write_context<W: Write>(out: &mut W, active: cortex_m::peripheral::scb::VectActive) {
+    use cortex_m::peripheral::scb::VectActive;
+
+    let _ = match active {
+        VectActive::ThreadMode => out.write_str("[thread mode] "),
+        VectActive::Exception(exception) => write!(out, "[exception {:?}] ", exception),
+        VectActive::Interrupt { irqn } => write!(out, "[irq {}] ", irqn),
+    };
+}
+
+/// Hook that writes the panic like the default hook followed by a line with the stack pointer,
+/// link register and program counter, `SP=0x20001fd0 LR=0x08000437 PC=0x08000412`
+///
+/// The registers are read inside the hook, not where the panic happened. The stack pointer is
+/// the active one (MSP or PSP) and shows how deep the stack went, the program counter points into
+/// the hook and the link register usually points into the panic machinery that called it. Neither
+/// tells where the panic happened, use the location for that. For the registers at the time of a
+/// fault use a fault handler instead, see [`handle_panic`](crate::handle_panic). When not running
+/// on a Cortex-M core only the panic is written.
+#[cfg(feature = "cortex-m")]
+pub fn regs_hook<W: Write>(out: &mut W, info: &PanicInfo) {
+    // read the link register before any call in the hook overwrites it
+    #[cfg(target_arch = "arm")]
+    let lr = read_lr();
+    crate::default_hook(out, info);
+    #[cfg(target_arch = "arm")]
+    {
+        use cortex_m::peripheral::{scb::VectActive, SCB};
+        use cortex_m::register::{control, msp, pc, psp};
+
+        // exception handlers always run on the main stack
+        let thread_mode = SCB::vect_active() == VectActive::ThreadMode;
+        let sp = match control::read().spsel() {
+            control::Spsel::Psp if thread_mode => psp::read(),
+            _ => msp::read(),
+        };
+        write_regs(out, sp, lr, pc::read());
+    }
+}
+
+/// Read the link register of the calling function
+///
+/// `cortex_m::register::lr::read` is only `#[inline]`, when it isn't inlined it returns an address
+/// in the function that called it.
+#[cfg(all(feature = "cortex-m", target_arch = "arm"))]
+#[inline(always)]
+fn read_lr() -> u32 {
+    let lr;
+    unsafe {
+        core::arch::asm!("mov {}, lr", out(reg) lr, options(nomem, nostack, preserves_flags))
+    };
+    lr
+}
+
+#[cfg(all(feature = "cortex-m", target_arch = "arm"))]
+fn write_regs<W: Write>(out: &mut W, sp: u32, lr: u32, pc: u32) {
+    let _ = write!(out, "\nSP={:#010x} LR={:#010x} PC={:#010x}", sp, lr, pc);
+}
+
+/// The link register at the entry of the `#[panic_handler]`
+#[cfg(all(feature = "cortex-m", target_arch = "arm"))]
+static ENTRY_LR: core::sync::atomic::AtomicU32 = core::sync::atomic::AtomicU32::new(0);
+
+/// Record the return address of the panic handler for [`pc_hook`]
+///
+/// The `#[panic_handler]` defined by this crate does this itself. A custom panic handler that
+/// calls [`handle_panic`](crate::handle_panic) has to call this before it calls anything else,
+/// any call overwrites the link register:
+///
+/// ```ignore
+/// use core::panic::PanicInfo;
+///
+/// #[panic_handler]
+/// fn panic(info: &PanicInfo) -> ! {
+///     panic_write::format::record_entry();
+///     panic_write::handle_panic(info)
+/// }
+/// ```
+///
+/// This is always inlined and reads the link register directly, so it's the return address of
+/// the function it's called from. When not running on a Cortex-M core this does nothing.
+#[cfg(feature = "cortex-m")]
+#[inline(always)]
+pub fn record_entry() {
+    #[cfg(target_arch = "arm")]
+    ENTRY_LR.store(read_lr(), core::sync::atomic::Ordering::Release);
+}
+
+/// Hook that writes the panic like the default hook followed by the return address of the panic
+/// handler as `PC=0x08000437`
+///
+/// The address is read from the link register on entry of the `#[panic_handler]`, before
+/// anything can overwrite it, and can be resolved against the ELF, e.g. with
+/// `addr2line -e app 0x08000437`. It points into the code that called the panic handler, which is
+/// usually `core::panicking` rather than the `panic!` itself, so walk up the stack from there in
+/// a debugger to find the call site. With a custom panic handler the address is only recorded if
+/// it calls [`record_entry`], otherwise `PC=0x00000000` is written. When not running on a
+/// Cortex-M core only the panic is written.
+#[cfg(feature = "cortex-m")]
+pub fn pc_hook<W: Write>(out: &mut W, info: &PanicInfo) {
+    crate::default_hook(out, info);
+    #[cfg(target_arch = "arm")]
+    write_pc(out, ENTRY_LR.load(core::sync::atomic::Ordering::Acquire));
+}
+
+#[cfg(all(feature = "cortex-m", any(target_arch = "arm", test)))]
+fn write_pc<W: Write>(out: &mut W, pc: u32) {
+    let _ = write!(out, "\nPC={:#010x}", pc);
+}
+
+/// Combine several hooks into one that runs them in order, writing `separator` between them
+///
+/// ```ignore
+/// let hook = format::chain([format::headline_hook, format::labeled_hook], "\n");
+/// let handler = pin!(PanicHandler::new_with_hook(serial, hook));
+/// ```
+pub fn chain<W: Write, const N: usize>(
+    hooks: [fn(&mut W, &PanicInfo); N],
+    separator: &'static str,
+) -> impl FnMut(&mut W, &PanicInfo) {
+    move |out: &mut W, info: &PanicInfo| {
+        for (i, hook) in hooks.iter().enumerate() {
+            if i > 0 {
+                let _ = out.write_str(separator);
+            }
+            hook(out, info);
+        }
+    }
+}
+
+/// Written in place of an empty panic message, so there's always some output
+const NO_INFO: &str = "panic (no info)";
+
+/// Write the panic message, or a fixed marker if the message is empty
+pub(crate) fn write_message<W: Write>(out: &mut W, info: &PanicInfo) {
+    let mut out = NonEmpty {
+        out,
+        written: false,
+    };
+    let _ = match crate::message_str(info) {
+        Some(message) => out.write_str(message),
+        None => write!(out, "{}", info.message()),
+    };
+    if !out.written {
+        let _ = out.out.write_str(NO_INFO);
+    }
+}
+
+/// Writer adapter that tracks whether anything was written
+struct NonEmpty<'a, W: Write> {
+    out: &'a mut W,
+    written: bool,
+}
+
+impl<'a, W: Write> Write for NonEmpty<'a, W> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.written |= !s.is_empty();
+        self.out.write_str(s)
+    }
+}
+
+/// Writer adapter that escapes everything written for use in a json string
+struct JsonEscape<'a, W: Write>(&'a mut W);
+
+impl<'a, W: Write> Write for JsonEscape<'a, W> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let mut start = 0;
+        for (i, byte) in s.bytes().enumerate() {
+            let escaped = match byte {
+                b'"' => "\\\"",
+                b'\\' => "\\\\",
+                b'\n' => "\\n",
+                b'\r' => "\\r",
+                b'\t' => "\\t",
+                0..=0x1f => "",
+                _ => continue,
+            };
+            self.0.write_str(&s[start..i])?;
+            if escaped.is_empty() {
+                write!(self.0, "\\u{:04x}", byte)?;
+            } else {
+                self.0.write_str(escaped)?;
+            }
+            start = i + 1;
+        }
+        self.0.write_str(&s[start..])
+    }
+}
+
+/// Write `bytes` as a hex dump, 16 bytes per line, starting at address `addr`
+///
+/// Every line has the form `20000000: 68 65 6c 6c 6f ... |hello...|`, bytes that aren't
+/// printable ascii are shown as `.`.
+pub fn hex_dump<W: Write>(out: &mut W, addr: usize, bytes: &[u8]) {
+    for (line, chunk) in bytes.chunks(16).enumerate() {
+        let _ = write!(out, "{:08x}: ", addr.wrapping_add(line * 16));
+        for i in 0..16 {
+            let _ = match chunk.get(i) {
+                Some(byte) => write!(out, "{:02x} ", byte),
+                None => out.write_str("   "),
+            };
+        }
+        let _ = out.write_char('|');
+        for byte in chunk {
+            let _ = match byte {
+                0x20..=0x7e => out.write_char(*byte as char),
+                _ => out.write_char('.'),
+            };
+        }
+        let _ = out.write_str("|\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulate_panic_with;
+    use crate::tests::globals;
+    use crate::PanicHandler;
+    use core::panic::Location;
+
+    #[test]
+    fn writes_the_location_before_the_message() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_hook(String::new(), location_hook));
+        let registration = handler.register();
+        let location = Location::caller();
+        simulate_panic_with(format_args!("boom"), Some(location));
+        assert_eq!(
+            *registration,
+            format!(
+                "PANIC {}:{}:{} boom",
+                location.file(),
+                location.line(),
+                location.column()
+            )
+        );
+    }
+
+    #[test]
+    fn writes_unknown_without_a_location() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_hook(String::new(), location_hook));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(*registration, "PANIC <unknown> boom");
+    }
+
+    #[test]
+    fn json_hook_escapes_the_message() {
+        let location = Location::caller();
+        let mut out = String::new();
+        json_hook(
+            &mut out,
+            &PanicInfo::new(format_args!("bad \"key\"\n\u{1}"), Some(location)),
+        );
+        assert_eq!(
+            out,
+            format!(
+                r#"{{"level":"panic","file":"{}","line":{},"msg":"bad \"key\"\n\u0001"}}"#,
+                location.file(),
+                location.line()
+            )
+        );
+    }
+
+    #[test]
+    fn json_hook_without_a_location() {
+        let mut out = String::new();
+        json_hook(&mut out, &PanicInfo::new(format_args!("boom"), None));
+        assert_eq!(
+            out,
+            r#"{"level":"panic","file":null,"line":null,"msg":"boom"}"#
+        );
+    }
+
+    #[test]
+    fn hex_dump_pads_the_last_line() {
+        let mut out = String::new();
+        hex_dump(&mut out, 0x2000_0000, b"hello, world!\n\0\xffabc");
+        assert_eq!(
+            out,
+            "20000000: 68 65 6c 6c 6f 2c 20 77 6f 72 6c 64 21 0a 00 ff |hello, world!...|\n\
+             20000010: 61 62 63                                        |abc|\n"
+        );
+    }
+
+    #[test]
+    fn hex_dump_from_a_hook() {
+        static STATE: [u8; 4] = *b"\xde\xad\xbe\xef";
+
+        fn dump_hook(out: &mut String, info: &PanicInfo) {
+            let _ = writeln!(out, "{}", info.message());
+            hex_dump(out, 0x100, &STATE);
+        }
+
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_hook(String::new(), dump_hook));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(
+            *registration,
+            format!("boom\n00000100: de ad be ef {}|....|\n", "   ".repeat(12))
+        );
+    }
+
+    #[test]
+    fn labeled_hook_writes_two_lines() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_hook(String::new(), labeled_hook));
+        let registration = handler.register();
+        let location = Location::caller();
+        simulate_panic_with(format_args!("boom"), Some(location));
+        assert_eq!(
+            *registration,
+            format!("MSG: boom\nAT: {}:{}", location.file(), location.line())
+        );
+    }
+
+    #[test]
+    fn labeled_hook_without_a_location() {
+        let mut out = String::new();
+        labeled_hook(&mut out, &PanicInfo::new(format_args!("boom"), None));
+        assert_eq!(out, "MSG: boom\nAT: <none>");
+    }
+
+    #[test]
+    fn empty_message_is_replaced() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_hook(String::new(), location_hook));
+        let registration = handler.register();
+        simulate_panic_with(format_args!(""), None);
+        assert_eq!(*registration, "PANIC <unknown> panic (no info)");
+    }
+
+    #[test]
+    fn formatted_empty_message_is_replaced() {
+        let empty = std::hint::black_box("");
+        let mut out = String::new();
+        json_hook(&mut out, &PanicInfo::new(format_args!("{}", empty), None));
+        assert_eq!(
+            out,
+            r#"{"level":"panic","file":null,"line":null,"msg":"panic (no info)"}"#
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "cortex-m")]
+    fn context_hook_has_no_prefix_off_target() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_hook(String::new(), context_hook));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        let mut expected = String::new();
+        crate::default_hook(&mut expected, &PanicInfo::new(format_args!("boom"), None));
+        assert_eq!(*registration, expected);
+    }
+
+    #[test]
+    fn stable_hook_writes_the_message_then_the_location() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_hook(String::new(), stable_hook));
+        let registration = handler.register();
+        let location = Location::caller();
+        simulate_panic_with(format_args!("boom"), Some(location));
+        assert_eq!(
+            *registration,
+            format!(
+                "panicked at 'boom', {}:{}:{}",
+                location.file(),
+                location.line(),
+                location.column()
+            )
+        );
+    }
+
+    #[test]
+    fn stable_hook_without_a_location() {
+        let mut out = String::new();
+        stable_hook(&mut out, &PanicInfo::new(format_args!("boom"), None));
+        assert_eq!(out, "panicked at 'boom'");
+    }
+
+    #[test]
+    #[cfg(feature = "cortex-m")]
+    fn regs_hook_only_writes_the_panic_off_target() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_hook(String::new(), regs_hook));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(*registration, "panicked at :\nboom");
+    }
+
+    #[test]
+    #[cfg(feature = "cortex-m")]
+    fn pc_hook_only_writes_the_panic_off_target() {
+        let _globals = globals();
+        // there's no link register to read on the host
+        record_entry();
+        let handler = core::pin::pin!(PanicHandler::new_with_hook(String::new(), pc_hook));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(*registration, "panicked at :\nboom");
+    }
+
+    #[test]
+    #[cfg(feature = "cortex-m")]
+    fn pc_is_written_as_a_full_width_address() {
+        let mut out = String::new();
+        write_pc(&mut out, 0x0800_0437);
+        write_pc(&mut out, 0x1a);
+        assert_eq!(out, "\nPC=0x08000437\nPC=0x0000001a");
+    }
+
+    #[test]
+    fn headline_comes_before_the_message() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_headline(
+            String::new(),
+            location_hook,
+            headline_hook
+        ));
+        let registration = handler.register();
+        let location = Location::caller();
+        simulate_panic_with(format_args!("boom"), Some(location));
+        assert_eq!(
+            *registration,
+            format!(
+                "PANIC@{file}:{line}\nPANIC {file}:{line}:{column} boom",
+                file = location.file(),
+                line = location.line(),
+                column = location.column()
+            )
+        );
+    }
+
+    #[test]
+    fn basename_hook_strips_the_directories() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_hook(String::new(), basename_hook));
+        let registration = handler.register();
+        let location = Location::caller();
+        simulate_panic_with(format_args!("boom"), Some(location));
+        assert_eq!(
+            *registration,
+            format!("format.rs:{}:{} boom", location.line(), location.column())
+        );
+    }
+
+    #[test]
+    fn basename_of_a_unix_path() {
+        assert_eq!(basename("/home/ci/app/src/main.rs"), "main.rs");
+    }
+
+    #[test]
+    fn basename_of_a_windows_path() {
+        assert_eq!(basename(r"C:\Users\ci\app\src\main.rs"), "main.rs");
+    }
+
+    #[test]
+    fn basename_of_a_bare_file_name() {
+        assert_eq!(basename("main.rs"), "main.rs");
+    }
+
+    #[test]
+    fn chain_runs_the_hooks_in_order_with_the_separator() {
+        let _globals = globals();
+        let hook = chain([location_hook, headline_hook], " | ");
+        let handler = core::pin::pin!(PanicHandler::new_with_hook(String::new(), hook));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(*registration, "PANIC <unknown> boom | PANIC@<unknown>");
+    }
+}
@@ -0,0 +1,66 @@
+//! A `PanicInfo` that can be created outside of a panic handler, used in place of the one from
+//! `core` to run the handler in tests and with the `simulate` feature
+
+use core::fmt::{self, Arguments, Display, Formatter};
+use core::panic::Location;
+
+/// Information about a panic, with the same methods as `core::panic::PanicInfo`
+///
+/// Unlike the `PanicInfo` from `core` this can be created from a message and location with
+/// [`new`](PanicInfo::new), so the handler can be run without panicking.
+#[derive(Debug, Clone, Copy)]
+pub struct PanicInfo<'a> {
+    message: Arguments<'a>,
+    location: Option<&'a Location<'a>>,
+}
+
+impl<'a> PanicInfo<'a> {
+    /// Describe a panic with the given message and location
+    pub const fn new(message: Arguments<'a>, location: Option<&'a Location<'a>>) -> Self {
+        PanicInfo { message, location }
+    }
+
+    /// The location the panic happened at, if known
+    pub fn location(&self) -> Option<&'a Location<'a>> {
+        self.location
+    }
+
+    /// The panic message
+    pub fn message(&self) -> PanicMessage<'a> {
+        PanicMessage {
+            message: self.message,
+        }
+    }
+}
+
+/// Written the same way as the `PanicInfo` from `core`, `panicked at <file>:<line>:<col>:` and
+/// the message on the next line
+impl Display for PanicInfo<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("panicked at ")?;
+        if let Some(location) = self.location {
+            location.fmt(f)?;
+        }
+        f.write_str(":\n")?;
+        f.write_fmt(self.message)
+    }
+}
+
+/// The message of a panic, see [`PanicInfo::message`]
+#[derive(Debug, Clone, Copy)]
+pub struct PanicMessage<'a> {
+    message: Arguments<'a>,
+}
+
+impl<'a> PanicMessage<'a> {
+    /// The message, if it's a plain string without any formatting arguments
+    pub fn as_str(&self) -> Option<&'static str> {
+        self.message.as_str()
+    }
+}
+
+impl Display for PanicMessage<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_fmt(self.message)
+    }
+}
@@ -1,35 +1,643 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
+use action::Outcome;
 use core::fmt::Write;
-use core::marker::PhantomPinned;
-use core::mem::{transmute, MaybeUninit};
-use core::panic::PanicInfo;
+use core::marker::{PhantomData, PhantomPinned};
+use core::mem::MaybeUninit;
 use core::pin::Pin;
-use core::ptr::null_mut;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use registry::{Finisher, Node, Poller, Trampoline};
+use writers::{ContextWriter, FeedingWriter, SliceWriter};
 
-static mut PANIC_HANDLER_GETTER: Option<unsafe fn(handler: *mut (), info: &PanicInfo)> = None;
-static mut PANIC_HANDLER: *mut () = null_mut();
+mod action;
+mod builder;
+#[cfg(feature = "defmt")]
+mod defmt_hook;
+mod early;
+#[cfg(feature = "cortex-m-rt")]
+mod fault;
+pub mod format;
+#[cfg(any(test, feature = "simulate"))]
+mod info;
+mod macros;
+mod persistent;
+mod registry;
+#[cfg(any(target_has_atomic = "8", feature = "critical-section"))]
+mod static_handler;
+#[cfg(feature = "ufmt")]
+mod ufmt_hook;
+pub mod writers;
+
+#[cfg(feature = "cortex-m")]
+pub use action::set_reset_delay;
+pub use action::{set_action, set_idle, set_resume, PanicAction};
+pub use builder::PanicHandlerBuilder;
+/// The information about a panic that's passed to the hooks
+///
+/// This is `core::panic::PanicInfo`, unless the `simulate` feature is enabled. That replaces it
+/// with a type with the same methods that can be created outside of a panic handler, see
+/// [`simulate_panic`], so hooks should name it through this crate.
+#[cfg(not(any(test, feature = "simulate")))]
+pub use core::panic::PanicInfo;
+#[cfg(feature = "defmt")]
+pub use defmt_hook::{defmt_hook, defmt_tee_hook};
+pub use early::set_early_writer;
+#[cfg(feature = "cortex-m-rt")]
+pub use fault::report_hard_fault;
+#[cfg(any(test, feature = "simulate"))]
+pub use info::{PanicInfo, PanicMessage};
+pub use persistent::{
+    last_panic, CrashRing, CrashRingWriter, PersistentWriter, PERSISTENT_CAPACITY,
+};
+#[cfg(any(target_has_atomic = "8", feature = "critical-section"))]
+pub use static_handler::StaticPanicHandler;
+#[cfg(feature = "ufmt")]
+pub use ufmt_hook::{ufmt_hook, UfmtWriter};
+
+/// Typestate of a [`PanicHandler`] that isn't registered, it can be set up and registered
+pub enum Unregistered {}
+
+/// Typestate of a registered [`PanicHandler`], reached through a [`Registration`] or
+/// [`PanicHandler::register_static`]
+///
+/// Only a registered handler can be detached, and it can't be registered again or have its writer
+/// borrowed with [`get_inner`](PanicHandler::get_inner).
+pub enum Registered {}
+
+/// Error returned by [`PanicHandler::try_register`] when a handler is already registered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlreadyRegistered;
+
+impl core::fmt::Display for AlreadyRegistered {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("a panic handler is already registered")
+    }
+}
+
+/// Error returned by [`PanicHandler::try_new`] when the writer can't be used to write panics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandlerError {
+    /// The writer has no room for any output, e.g. a [`SliceWriter`] over an empty buffer
+    NoCapacity,
+}
+
+impl core::fmt::Display for HandlerError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            HandlerError::NoCapacity => f.write_str("the writer has no room for any output"),
+        }
+    }
+}
+
+/// The number of panics currently being handled, to detect panics from within the hook or writer
+static DEPTH: AtomicU8 = AtomicU8::new(0);
+/// How deep panics are still written by the handlers, see [`set_max_panic_depth`]
+static MAX_DEPTH: AtomicU8 = AtomicU8::new(1);
+static PANIC_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of times the panic handler ran since startup, including nested panics
+pub fn panic_count() -> usize {
+    PANIC_COUNT.load(Ordering::Acquire)
+}
+
+/// Whether the panic handler is running, e.g. for interrupt handlers to stop touching
+/// peripherals that are used to write the panic message
+///
+/// Once set this stays set while the core halts. When a resume function is set with
+/// [`set_resume`] the depth is reset to 0 right before it's called, so this is false again once
+/// the program continues, `simulate_panic` resets it the same way.
+pub fn is_panicking() -> bool {
+    panic_depth() > 0
+}
+
+/// The number of panics currently being handled, more than 1 if the hook or writer panicked
+pub fn panic_depth() -> u8 {
+    DEPTH.load(Ordering::Acquire)
+}
+
+/// Set up to which depth panics are written by the registered handlers, the default is 1
+///
+/// With the default only the first panic is written, a panic from within the hook or writer only
+/// finishes its output, e.g. resetting the terminal color, before halting. With a higher limit
+/// nested panics are written too, marked with `nested panic: `, which can show what went wrong in
+/// the hook. Panics past the limit finish the output once and go straight to the post-panic
+/// action after that.
+pub fn set_max_panic_depth(depth: u8) {
+    MAX_DEPTH.store(depth, Ordering::Release);
+}
+
+/// Whether a panic handler is currently registered
+pub fn is_registered() -> bool {
+    registry::top().is_some()
+}
+
+/// Run `f` with a panic handler for `writer` registered, returning the writer afterwards
+///
+/// Panics in `f` are written to `writer` using `hook`, once `f` returns the handler is
+/// deregistered again.
+///
+/// ```ignore
+/// let serial = panic_write::with_handler(serial, format::location_hook, || {
+///     run_app();
+/// });
+/// ```
+pub fn with_handler<W: Write, F: FnMut(&mut W, &PanicInfo)>(
+    writer: W,
+    hook: F,
+    f: impl FnOnce(),
+) -> W {
+    let handler = core::pin::pin!(PanicHandler::new_with_hook(writer, hook));
+    let registration = handler.register();
+    f();
+    registration.detach()
+}
+
+/// The address of the writer of the most recently registered panic handler, for debugging
+/// registration issues
+pub fn current_writer_addr() -> Option<NonNull<()>> {
+    // the writer is at the start of the handler
+    registry::top().and_then(NonNull::new)
+}
+
+/// The panic message, if it's a plain string without any formatting arguments
+///
+/// This is the case for panics like `panic!("message")` or `unwrap` on `None`, but not for
+/// `panic!("value: {}", value)`. The string payloads `std` panics carry aren't available in a
+/// `#[panic_handler]`, so there is no fallback for formatted messages, use `info.message()` to
+/// format those.
+pub fn message_str(info: &PanicInfo) -> Option<&'static str> {
+    info.message().as_str()
+}
+
+/// Whether the panic message is a plain static string, see [`message_str`]
+///
+/// Panics with a static message always produce the same text, so a host can group them by
+/// message, unlike formatted messages that include runtime values. Whether a message without
+/// arguments is reported as static is decided by the compiler, e.g. `panic!("{}", "literal")` can
+/// be turned into a static string depending on the toolchain and optimization level.
+pub fn message_is_static(info: &PanicInfo) -> bool {
+    message_str(info).is_some()
+}
 
 /// Use monomorphization to "save" the type parameter of the static pointer
-unsafe fn trampoline<W: Write, F: FnMut(&mut W, &PanicInfo)>(ptr: *mut (), info: &PanicInfo) {
-    let handler: &mut PanicHandler<W, F> = transmute(ptr);
+///
+/// Every writer and hook type gets its own copy of this, so it only turns the handler into a
+/// [`Steps`] trait object for [`write_panic_dyn`], which is shared by all handlers.
+#[cold]
+#[inline(never)]
+unsafe fn trampoline<W: Write, F: FnMut(&mut W, &PanicInfo)>(
+    ptr: *mut (),
+    info: &PanicInfo,
+) -> Option<Outcome> {
+    let handler = &mut *(ptr as *mut PanicHandler<W, F>);
+    write_panic_dyn(handler, info)
+}
+
+#[cold]
+#[inline(never)]
+unsafe fn finisher<W: Write, F: FnMut(&mut W, &PanicInfo)>(ptr: *mut ()) {
+    let handler = &mut *(ptr as *mut PanicHandler<W, F>);
+    finish_output_dyn(handler);
+}
+
+#[cold]
+#[inline(never)]
+unsafe fn poller<W: Write, F: FnMut(&mut W, &PanicInfo)>(ptr: *mut ()) {
+    let handler = &mut *(ptr as *mut PanicHandler<W, F>);
+    poll_flush_dyn(handler);
+}
+
+/// A writer that buffers output and needs to be flushed before the core halts
+///
+/// Pass `Flush::flush` to [`PanicHandler::new_with_flush`] to have the handler flush the writer
+/// after the panic message is written.
+pub trait Flush {
+    fn flush(&mut self);
+}
 
-    // safe because self.writer is only uninit during drop
-    let writer: &mut W = { &mut *handler.writer.as_mut_ptr() };
+/// A writer that needs to know about the start and end of the panic message
+///
+/// Writers for which this is implemented can be integrated with the handler using
+/// [`PanicHandler::new_panic_writer`] or [`PanicHandlerBuilder::panic_writer`], after which the
+/// handler calls [`finish`](PanicWriter::finish) once the hook is done and
+/// [`flush`](PanicWriter::flush) right before halting.
+pub trait PanicWriter: Write {
+    /// Flush any buffered output
+    fn flush(&mut self) {}
 
-    (handler.hook)(writer, info)
+    /// Write any output that has to follow the panic message, like terminal sequences
+    fn finish(&mut self) {}
 }
 
-pub struct PanicHandler<W: Write, F: FnMut(&mut W, &PanicInfo)> {
+/// A writer that can check whether it's usable as a panic sink, see [`PanicHandler::try_new`]
+pub trait Validate {
+    fn validate(&self) -> Result<(), HandlerError>;
+}
+
+// the writer is the first field, so the registered pointer to the handler is also the address of
+// the writer, see `current_writer_addr`
+//
+// both states have the same layout, so a handler can be viewed in the registered state while it is
+#[repr(C)]
+pub struct PanicHandler<W: Write, F: FnMut(&mut W, &PanicInfo), S = Unregistered> {
     writer: MaybeUninit<W>,
+    /// Whether `writer` is initialized, it's taken out by `detach`
+    initialized: bool,
     hook: F,
+    options: Options<W>,
+    node: Node,
     _pin: PhantomPinned,
+    _state: PhantomData<S>,
+}
+
+type SetLimit<W> = fn(&mut W, usize);
+type Field<W> = (&'static str, fn(&mut W));
+type WriteSentinel<W> = fn(&mut W);
+
+/// The number of fields that can be added with [`PanicHandlerBuilder::field`]
+pub const MAX_FIELDS: usize = 4;
+
+/// The optional features of a handler, shared with the builder
+struct Options<W> {
+    common: CommonOptions,
+    flush: Option<fn(&mut W)>,
+    /// A non-blocking flush that is polled before the post-panic action
+    #[cfg(feature = "nb")]
+    poll_flush: Option<PollFlush<W>>,
+    finish: Option<fn(&mut W)>,
+    headline: Option<fn(&mut W, &PanicInfo)>,
+    /// Functions writing the sentinels before and after the output
+    framing: Option<(WriteSentinel<W>, WriteSentinel<W>)>,
+    context: Option<fn(&mut W)>,
+    device_id: Option<fn(&mut W)>,
+    fields: [Option<Field<W>>; MAX_FIELDS],
+    /// The limit set by the builder and how to apply it to the writer
+    max_bytes: Option<(usize, SetLimit<W>)>,
+}
+
+/// A non-blocking flush with its error type erased, see
+/// [`PanicHandlerBuilder::poll_flush`]
+#[cfg(feature = "nb")]
+struct PollFlush<W> {
+    /// The `fn(&mut W) -> nb::Result<(), E>` passed to the builder
+    flush: *const (),
+    /// Calls `flush` as the function it was created from, returns whether the flush completed
+    poll: unsafe fn(flush: *const (), writer: &mut W) -> bool,
+    max_polls: u32,
+}
+
+#[cfg(feature = "nb")]
+impl<W> Clone for PollFlush<W> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+#[cfg(feature = "nb")]
+impl<W> Copy for PollFlush<W> {}
+
+#[cfg(feature = "nb")]
+impl<W> PollFlush<W> {
+    fn new<E>(flush: fn(&mut W) -> nb::Result<(), E>, max_polls: u32) -> Self {
+        unsafe fn poll<W, E>(flush: *const (), writer: &mut W) -> bool {
+            // safe because `flush` was created from a function of this type
+            let flush: fn(&mut W) -> nb::Result<(), E> = core::mem::transmute(flush);
+            !matches!(flush(writer), Err(nb::Error::WouldBlock))
+        }
+
+        PollFlush {
+            flush: flush as *const (),
+            poll: poll::<W, E>,
+            max_polls,
+        }
+    }
+
+    /// Poll the flush until it completes, or gives up after `max_polls` polls
+    fn wait(&self, writer: &mut W) {
+        for _ in 0..self.max_polls {
+            if unsafe { (self.poll)(self.flush, writer) } {
+                break;
+            }
+        }
+    }
+}
+
+/// The optional features that don't depend on the writer type, used by [`write_panic_dyn`]
+struct CommonOptions {
+    disable_watchdog: Option<fn()>,
+    side_effect: Option<fn(&PanicInfo)>,
+    action: Option<PanicAction>,
+    /// Replaces the global reset delay when this handler's action is taken
+    #[cfg(feature = "cortex-m")]
+    reset_delay: Option<u32>,
+    prefix: Option<&'static str>,
+    banner: Option<&'static str>,
+    suffix: Option<&'static str>,
+    line_ending: Option<&'static str>,
+    clock: Option<fn() -> u64>,
+    stats_header: Option<fn() -> u64>,
+    summary: Option<&'static mut dyn Write>,
 }
 
+impl<W> Options<W> {
+    const fn new() -> Self {
+        Options {
+            common: CommonOptions {
+                disable_watchdog: None,
+                side_effect: None,
+                action: None,
+                #[cfg(feature = "cortex-m")]
+                reset_delay: None,
+                prefix: None,
+                banner: None,
+                suffix: None,
+                line_ending: None,
+                clock: None,
+                stats_header: None,
+                summary: None,
+            },
+            flush: None,
+            #[cfg(feature = "nb")]
+            poll_flush: None,
+            finish: None,
+            headline: None,
+            framing: None,
+            context: None,
+            device_id: None,
+            fields: [None; MAX_FIELDS],
+            max_bytes: None,
+        }
+    }
+}
+
+#[cfg(any(
+    all(feature = "hook-minimal", feature = "hook-default"),
+    all(feature = "hook-minimal", feature = "hook-message"),
+    all(feature = "hook-minimal", feature = "location-only"),
+    all(feature = "hook-default", feature = "hook-message"),
+    all(feature = "hook-default", feature = "location-only"),
+    all(feature = "hook-message", feature = "location-only"),
+))]
+compile_error!(
+    "only one of the `hook-minimal`, `hook-default`, `hook-message` and `location-only` features \
+    can be enabled"
+);
+
+/// Write only the location of the panic
+#[cfg(feature = "hook-minimal")]
+fn default_hook<W: Write>(out: &mut W, info: &PanicInfo) {
+    let _ = match info.location() {
+        Some(location) => write!(out, "panicked at {}", location),
+        None => out.write_str("panicked"),
+    };
+}
+
+/// Write only `file:line:col`, without touching the message so its formatting code can be
+/// dropped by the linker
+#[cfg(all(feature = "location-only", not(feature = "hook-minimal")))]
+fn default_hook<W: Write>(out: &mut W, info: &PanicInfo) {
+    let _ = match info.location() {
+        Some(location) => write!(out, "{}", location),
+        None => out.write_str("<unknown>"),
+    };
+}
+
+/// Write only the panic message
+#[cfg(all(
+    feature = "hook-message",
+    not(any(feature = "hook-minimal", feature = "location-only"))
+))]
+fn default_hook<W: Write>(out: &mut W, info: &PanicInfo) {
+    format::write_message(out, info);
+}
+
+#[cfg(not(any(
+    feature = "hook-minimal",
+    feature = "hook-message",
+    feature = "location-only"
+)))]
 fn default_hook<W: Write>(out: &mut W, info: &PanicInfo) {
     let _ = write!(out, "{}", info);
 }
 
+impl<W: Write> PanicHandler<W, fn(&mut W, &PanicInfo)> {
+    /// Create a panic handler from a `core::fmt::Write` using the default hook
+    ///
+    /// See [`new_with_hook`](PanicHandler::new_with_hook) for details.
+    #[must_use = "the panic handler must be kept in scope"]
+    pub fn new(writer: W) -> Self {
+        PanicHandler::new_with_hook(writer, default_hook::<W>)
+    }
+
+    /// Create a panic handler using the default hook in a const context, e.g. in a `static`
+    ///
+    /// ```ignore
+    /// type Writer = SliceWriter<'static>;
+    ///
+    /// static mut BUFFER: [u8; 256] = [0; 256];
+    /// static mut HANDLER: PanicHandler<Writer, fn(&mut Writer, &PanicInfo)> =
+    ///     PanicHandler::new_const(SliceWriter::new(unsafe { &mut *addr_of_mut!(BUFFER) }));
+    ///
+    /// let handler = PanicHandler::register_static(unsafe { &mut *addr_of_mut!(HANDLER) });
+    /// ```
+    #[must_use = "the panic handler must be kept in scope"]
+    pub const fn new_const(writer: W) -> Self {
+        PanicHandler::new_with_hook(writer, default_hook::<W> as fn(&mut W, &PanicInfo))
+    }
+
+    /// Create a panic handler after checking that the writer can actually hold the panic
+    ///
+    /// This catches setup mistakes, like a [`SliceWriter`] over an empty buffer, that would
+    /// otherwise silently drop all panic output.
+    pub fn try_new(writer: W) -> Result<Self, HandlerError>
+    where
+        W: Validate,
+    {
+        writer.validate()?;
+        Ok(PanicHandler::new(writer))
+    }
+
+    /// Create a panic handler with a hook that can consume resources, it's only called for the
+    /// first panic
+    ///
+    /// Nested panics or panics after the handler was re-registered only get the optional
+    /// features like the prefix, since the hook was already taken.
+    #[must_use = "the panic handler must be kept in scope"]
+    pub fn new_with_once_hook<G: FnOnce(&mut W, &PanicInfo)>(
+        writer: W,
+        hook: G,
+    ) -> PanicHandler<W, impl FnMut(&mut W, &PanicInfo)> {
+        let mut hook = Some(hook);
+        PanicHandler::new_with_hook(writer, move |out: &mut W, info: &PanicInfo| {
+            if let Some(hook) = hook.take() {
+                hook(out, info);
+            }
+        })
+    }
+
+    /// Create a panic handler with a hook that decides the post-panic action, e.g. based on the
+    /// panic message
+    ///
+    /// The decision overrides any action set for the handler and the action set by
+    /// [`set_action`]. Handlers with a hook that doesn't return an action use those instead.
+    #[must_use = "the panic handler must be kept in scope"]
+    pub fn new_with_deciding_hook<G: FnMut(&mut W, &PanicInfo) -> PanicAction>(
+        writer: W,
+        mut hook: G,
+    ) -> PanicHandler<W, impl FnMut(&mut W, &PanicInfo)> {
+        PanicHandler::new_with_hook(writer, move |out: &mut W, info: &PanicInfo| {
+            action::decide(hook(out, info));
+        })
+    }
+
+    /// Create a panic handler that brackets the panic message with fixed markers
+    ///
+    /// This makes it easy for host side tools to find panic messages in a noisy stream, e.g.
+    /// `new_with_markers(serial, "===PANIC===\n", "\n===END===\n")`.
+    #[must_use = "the panic handler must be kept in scope"]
+    pub fn new_with_markers(writer: W, prefix: &'static str, suffix: &'static str) -> Self {
+        let mut handler = PanicHandler::new(writer);
+        handler.options.common.prefix = Some(prefix);
+        handler.options.common.suffix = Some(suffix);
+        handler
+    }
+}
+
+impl<'a, W: Write> PanicHandler<&'a mut W, fn(&mut &'a mut W, &PanicInfo)> {
+    /// Create a panic handler that borrows the writer instead of taking ownership of it
+    ///
+    /// The handler can't outlive the borrow so neither can its registration, once the handler is
+    /// dropped or detached the writer can be used directly again.
+    ///
+    /// Detaching gives back the exclusive borrow with its original lifetime, so the uart can be
+    /// handed back to the HAL while the handler is still in scope:
+    ///
+    /// ```ignore
+    /// let handler = pin!(PanicHandler::new_borrowed(&mut uart));
+    /// let registration = handler.register();
+    /// run_critical_section_of_app();
+    /// let uart = registration.detach();
+    /// uart.reconfigure(baud_rate);
+    /// ```
+    #[must_use = "the panic handler must be kept in scope"]
+    pub fn new_borrowed(writer: &'a mut W) -> Self {
+        PanicHandler::new(writer)
+    }
+}
+
+impl<'a> PanicHandler<SliceWriter<'a>, fn(&mut SliceWriter<'a>, &PanicInfo)> {
+    /// Create a panic handler that captures the panic message into `buffer`
+    ///
+    /// Output that doesn't fit in the buffer is dropped. Use
+    /// `registration.map_writer(SliceWriter::into_written)` to detach the handler and get the
+    /// captured bytes.
+    #[must_use = "the panic handler must be kept in scope"]
+    pub fn capture_into(buffer: &'a mut [u8]) -> Self {
+        PanicHandler::new(SliceWriter::new(buffer))
+    }
+}
+
+impl<W: Write, C> PanicHandler<ContextWriter<W, C>, fn(&mut ContextWriter<W, C>, &PanicInfo)> {
+    /// Create a panic handler with a hook that also gets mutable access to a context, e.g. a
+    /// peripheral to force a DMA transfer to complete
+    ///
+    /// The context is stored next to the writer, use
+    /// [`Registration::detach_with_ctx`] to detach the handler and get both back.
+    ///
+    /// ```ignore
+    /// let handler = pin!(PanicHandler::new_with_ctx(serial, dma, |out, dma, info| {
+    ///     dma.abort();
+    ///     let _ = write!(out, "{}", info);
+    /// }));
+    /// ```
+    #[must_use = "the panic handler must be kept in scope"]
+    #[allow(clippy::type_complexity)]
+    pub fn new_with_ctx<G: FnMut(&mut W, &mut C, &PanicInfo)>(
+        writer: W,
+        context: C,
+        mut hook: G,
+    ) -> PanicHandler<ContextWriter<W, C>, impl FnMut(&mut ContextWriter<W, C>, &PanicInfo)> {
+        PanicHandler::new_with_hook(
+            ContextWriter::new(writer, context),
+            move |out: &mut ContextWriter<W, C>, info: &PanicInfo| {
+                let (out, context) = out.parts();
+                hook(out, context, info);
+            },
+        )
+    }
+}
+
+impl<'a> PanicHandler<&'a mut dyn Write, fn(&mut &'a mut dyn Write, &PanicInfo)> {
+    /// Create a panic handler that writes through a trait object
+    ///
+    /// The part of the handler that calls the writer is instantiated for every writer type it's
+    /// used with, handlers created with this share a single instance for all writers. This trades
+    /// a virtual call per write for less code, for when flash is tight and multiple handlers with
+    /// different writers are used.
+    #[must_use = "the panic handler must be kept in scope"]
+    pub fn new_dyn(writer: &'a mut dyn Write) -> Self {
+        PanicHandler::new(writer)
+    }
+}
+
+impl<W: Write, Fp: FnMut(), F: FnMut(&mut FeedingWriter<W, Fp>, &PanicInfo)>
+    PanicHandler<FeedingWriter<W, Fp>, F>
+{
+    /// Create a panic handler that calls `feed` after every write, to keep a watchdog from
+    /// resetting the device while the panic message is written
+    ///
+    /// Use [`FeedingWriter::with_interval`] to feed the watchdog less often.
+    #[must_use = "the panic handler must be kept in scope"]
+    pub fn new_with_watchdog(writer: W, hook: F, feed: Fp) -> Self {
+        PanicHandler::new_with_hook(FeedingWriter::new(writer, feed), hook)
+    }
+}
+
+#[cfg(feature = "cortex-m")]
+impl<W: Write + Flush> PanicHandler<W, fn(&mut W, &PanicInfo)> {
+    /// The reset delay used by [`uart_reset`](PanicHandler::uart_reset), enough to drain a 16 byte
+    /// FIFO at 115200 baud on a core running at up to 64MHz
+    pub const UART_RESET_DELAY: u32 = 100_000;
+
+    /// Create a panic handler for the common setup of writing the panic to a uart and then
+    /// resetting the system
+    ///
+    /// The handler writes the panic with the default hook, flushes the writer with [`Flush`] and
+    /// resets with [`PanicAction::Reset`] after waiting
+    /// [`UART_RESET_DELAY`](PanicHandler::UART_RESET_DELAY) cycles. The delay belongs to the
+    /// handler and is only used when its action is taken, the global delay set with
+    /// [`set_reset_delay`] is left alone. Use
+    /// [`uart_reset_with_delay`](PanicHandler::uart_reset_with_delay) for slower baud rates or
+    /// faster cores.
+    #[must_use = "the panic handler must be kept in scope"]
+    pub fn uart_reset(writer: W) -> Self {
+        PanicHandler::uart_reset_with_delay(writer, Self::UART_RESET_DELAY)
+    }
+
+    /// Create a panic handler like [`uart_reset`](PanicHandler::uart_reset) that waits `cycles`
+    /// before resetting, see [`set_reset_delay`] for how to pick the delay
+    #[must_use = "the panic handler must be kept in scope"]
+    pub fn uart_reset_with_delay(writer: W, cycles: u32) -> Self {
+        let mut handler = PanicHandler::new(writer);
+        handler.options.flush = Some(<W as Flush>::flush);
+        handler.options.common.action = Some(PanicAction::Reset);
+        handler.options.common.reset_delay = Some(cycles);
+        handler
+    }
+}
+
+impl<W: PanicWriter, F: FnMut(&mut W, &PanicInfo)> PanicHandler<W, F> {
+    /// Create a panic handler that calls the [`PanicWriter`] methods of the writer
+    #[must_use = "the panic handler must be kept in scope"]
+    pub fn new_panic_writer(writer: W, hook: F) -> Self {
+        let mut handler = PanicHandler::new_with_hook(writer, hook);
+        handler.options.finish = Some(<W as PanicWriter>::finish);
+        handler.options.flush = Some(<W as PanicWriter>::flush);
+        handler
+    }
+}
+
 impl<W: Write, F: FnMut(&mut W, &PanicInfo)> PanicHandler<W, F> {
     /// Create a panic handler from a `core::fmt::Write`
     ///
@@ -39,78 +647,1589 @@ impl<W: Write, F: FnMut(&mut W, &PanicInfo)> PanicHandler<W, F> {
     /// Additionally, the panic handler implements `Deref` for the provided `Write` and can be used
     /// in place of the original `Write` throughout the app.
     #[must_use = "the panic handler must be kept in scope"]
-    pub fn new_with_hook(writer: W, hook: F) -> Self {
+    pub const fn new_with_hook(writer: W, hook: F) -> Self {
         PanicHandler {
             writer: MaybeUninit::new(writer),
+            initialized: true,
             hook,
+            options: Options::new(),
+            node: Node::new(
+                trampoline::<W, F>,
+                finisher::<W, F> as Finisher,
+                poller::<W, F> as Poller,
+            ),
             _pin: PhantomPinned,
+            _state: PhantomData,
         }
     }
 
-    pub fn new(writer: W) -> PanicHandler<W, fn(&mut W, &PanicInfo)> {
-        // Default Hook:
-        PanicHandler::<W, _>::new_with_hook(writer, default_hook::<W>)
+    /// Create a panic handler that flushes the writer after the panic message is written
+    ///
+    /// For writers implementing [`Flush`] the `flush` argument can simply be `Flush::flush`.
+    #[must_use = "the panic handler must be kept in scope"]
+    pub fn new_with_flush(writer: W, hook: F, flush: fn(&mut W)) -> Self {
+        let mut handler = PanicHandler::new_with_hook(writer, hook);
+        handler.options.flush = Some(flush);
+        handler
+    }
+
+    /// Create a panic handler that runs a side effect before the panic message is written
+    ///
+    /// The side effect runs even if writing the panic message fails, which makes it suitable for
+    /// things like turning on an error led.
+    #[must_use = "the panic handler must be kept in scope"]
+    pub fn new_with_side_effect(writer: W, hook: F, side_effect: fn(&PanicInfo)) -> Self {
+        let mut handler = PanicHandler::new_with_hook(writer, hook);
+        handler.options.common.side_effect = Some(side_effect);
+        handler
+    }
+
+    /// Create a panic handler that writes a timestamp before the panic message
+    ///
+    /// The `clock` is called when the panic happens and its value is written as `[<ticks>] `, it
+    /// can read whatever monotonic time source is available.
+    #[must_use = "the panic handler must be kept in scope"]
+    pub fn new_with_clock(writer: W, hook: F, clock: fn() -> u64) -> Self {
+        let mut handler = PanicHandler::new_with_hook(writer, hook);
+        handler.options.common.clock = Some(clock);
+        handler
+    }
+
+    /// Create a panic handler that writes a short line before the full panic message, e.g. with
+    /// [`format::headline_hook`]
+    ///
+    /// On an unreliable link the short line is likely to get through even if the connection
+    /// drops while the rest of the message is written, so at least the location survives.
+    #[must_use = "the panic handler must be kept in scope"]
+    pub fn new_with_headline(writer: W, hook: F, headline: fn(&mut W, &PanicInfo)) -> Self {
+        let mut handler = PanicHandler::new_with_hook(writer, hook);
+        handler.options.headline = Some(headline);
+        handler
     }
 
-    pub fn register(self: &mut Pin<&mut Self>) {
+    /// Register this handler as the active panic handler
+    ///
+    /// The handler stays registered until the returned [`Registration`] is dropped, the guard can
+    /// be used in place of the writer in the meantime.
+    ///
+    /// Multiple handlers can be registered at the same time, on panic every registered handler
+    /// is run, starting with the most recently registered one. The post-panic action of the most
+    /// recently registered handler that has one set is used.
+    pub fn register(self: Pin<&mut Self>) -> Registration<'_, W, F> {
+        // safe because the handler is only accessed through the pointer while registered
+        let handler = NonNull::from(unsafe { self.get_unchecked_mut() });
+        unsafe { Self::push(handler.as_ptr()) };
+
+        Registration {
+            handler: handler.cast(),
+            _borrow: PhantomData,
+        }
+    }
+
+    /// Register this handler, guaranteeing the registration is safe on multicore chips
+    ///
+    /// With the `critical-section` feature all changes to the registered handlers happen inside
+    /// a critical section, so cores registering handlers at the same time don't race. Every
+    /// registered handler runs on panic on either core, with the most recent registration first.
+    /// Use [`try_register`](PanicHandler::try_register) to detect that another core already
+    /// registered a handler instead.
+    #[cfg(feature = "critical-section")]
+    pub fn register_sync(self: Pin<&mut Self>) -> Registration<'_, W, F> {
+        self.register()
+    }
+
+    /// Register this handler only if no other handler is registered
+    ///
+    /// This fails for any registered handler, whatever its writer and hook types are, so it can be
+    /// used to guarantee a single active handler. Every node carries the trampoline for its own
+    /// handler type, so handlers of different types registered with
+    /// [`register`](PanicHandler::register) all run correctly. To send the panic to more than one
+    /// place from a single handler combine the writers with a
+    /// [`TeeWriter`](writers::TeeWriter) or [`MultiWriter`](writers::MultiWriter), or run extra
+    /// code with [`new_with_side_effect`](PanicHandler::new_with_side_effect).
+    ///
+    /// With the `critical-section` feature the check and registration are done in a single
+    /// critical section, so only one of multiple cores registering at the same time succeeds.
+    pub fn try_register(self: Pin<&mut Self>) -> Result<Registration<'_, W, F>, AlreadyRegistered> {
+        // safe because the handler is only accessed through the pointer while registered
+        let handler = NonNull::from(unsafe { self.get_unchecked_mut() });
+        let node = unsafe { core::ptr::addr_of_mut!((*handler.as_ptr()).node) };
+        if !unsafe { registry::try_push(node, handler.as_ptr() as *mut ()) } {
+            return Err(AlreadyRegistered);
+        }
+
+        Ok(Registration {
+            handler: handler.cast(),
+            _borrow: PhantomData,
+        })
+    }
+
+    /// Register a handler that lives for the rest of the program
+    ///
+    /// Unlike [`register`](PanicHandler::register) the handler is never deregistered, there is no
+    /// need to keep a guard in scope.
+    ///
+    /// ```ignore
+    /// use static_cell::StaticCell;
+    ///
+    /// static HANDLER: StaticCell<PanicHandler<Serial, fn(&mut Serial, &PanicInfo)>> =
+    ///     StaticCell::new();
+    ///
+    /// let handler = PanicHandler::register_static(HANDLER.init(PanicHandler::new(serial)));
+    /// ```
+    pub fn register_static(
+        handler: &'static mut Self,
+    ) -> Pin<&'static mut PanicHandler<W, F, Registered>> {
+        // keep using the pointer the handler was registered with, reborrowing `handler` after
+        // taking the pointer would invalidate it
+        let handler: *mut Self = handler;
         unsafe {
-            PANIC_HANDLER_GETTER = Some(trampoline::<W, F>);
-            PANIC_HANDLER = transmute(self.as_ref());
+            Self::push(handler);
+            Pin::static_mut(&mut *handler.cast())
+        }
+    }
+
+    /// Put a writer back into a detached handler and register it again
+    ///
+    /// Detaching leaves the handler without a writer, it doesn't write anything on panic until
+    /// it's reattached. This is for temporarily taking the writer out of a handler, e.g. to use
+    /// it with an api that needs it by value. Only an unregistered handler can be reattached, use
+    /// [`Registration::detach`] first. Returns the writer as an error if the handler isn't
+    /// detached.
+    pub fn reattach(handler: Pin<&mut Self>, writer: W) -> Result<Registration<'_, W, F>, W> {
+        if handler.initialized {
+            return Err(writer);
         }
+        // the writer isn't pinned, so initializing it doesn't move the handler
+        let inner = unsafe { handler.get_unchecked_mut() };
+        inner.writer = MaybeUninit::new(writer);
+        inner.initialized = true;
+        Ok(unsafe { Pin::new_unchecked(inner) }.register())
+    }
+
+    pub fn get_inner(self: Pin<&mut Self>) -> &mut W {
+        unsafe { self.get_unchecked_mut() }
+    }
+
+    /// Access the writer of a handler that isn't pinned, e.g. during setup
+    ///
+    /// A handler can only be registered through a `Pin`, and `PanicHandler` isn't `Unpin`, so a
+    /// plain `&mut` to the handler guarantees it isn't registered.
+    pub fn get_inner_unpinned(&mut self) -> &mut W {
+        self
+    }
+
+    /// Push the handler onto the registered handler stack
+    ///
+    /// # Safety
+    ///
+    /// The handler must be pinned and stay valid until it's removed from the stack.
+    unsafe fn push(handler: *mut Self) {
+        registry::push(core::ptr::addr_of_mut!((*handler).node), handler as *mut ());
     }
+}
 
-    /// Detach this panic handler and return the underlying writer
+impl<W: Write, F: FnMut(&mut W, &PanicInfo)> PanicHandler<W, F, Registered> {
+    /// Deregister this panic handler and return the underlying writer
+    ///
+    /// Only a registered handler can be detached, [`Registration::detach`] does this for a
+    /// handler registered with [`register`](PanicHandler::register).
+    ///
+    /// The handler can still be reached through the pin afterwards, accessing its writer then
+    /// panics instead of reading the moved out writer. Prefer [`Registration::detach`], which
+    /// consumes the guard so the handler can't be used through it after the writer is taken out.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handler was already detached, use [`try_detach`](PanicHandler::try_detach)
+    /// if that can happen.
     pub fn detach(handler: Pin<&mut Self>) -> W {
-        unsafe {
-            PANIC_HANDLER_GETTER = None;
-            PANIC_HANDLER = null_mut();
+        match Self::try_detach(handler) {
+            Some(writer) => writer,
+            None => panic!("panic handler is already detached"),
+        }
+    }
 
+    /// Deregister this panic handler and return the underlying writer, or `None` if the handler
+    /// was already detached
+    pub fn try_detach(handler: Pin<&mut Self>) -> Option<W> {
+        unsafe {
             // unpin is safe because the pointer to the handler is removed
             let handler = Pin::into_inner_unchecked(handler);
+            registry::remove(&mut handler.node);
+
+            if !handler.initialized {
+                return None;
+            }
+            handler.initialized = false;
             let writer = core::mem::replace(&mut handler.writer, MaybeUninit::uninit());
 
-            // safe because self.writer is only uninit during drop
-            writer.assume_init()
+            // safe because the writer is initialized until it's detached
+            Some(writer.assume_init())
         }
     }
 
-    pub fn get_inner(self: Pin<&mut Self>) -> &mut W {
-        unsafe { self.get_unchecked_mut() }
+    /// Detach this panic handler and transform the underlying writer, e.g. `|w| w.into_bytes()`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handler was already detached, like [`detach`](PanicHandler::detach).
+    pub fn map_writer<U>(handler: Pin<&mut Self>, f: impl FnOnce(W) -> U) -> U {
+        f(Self::detach(handler))
+    }
+}
+
+impl<W: Write, F: FnMut(&mut W, &PanicInfo), S> PanicHandler<W, F, S> {
+    /// Replace the hook, the handler stays registered if it was
+    ///
+    /// The new hook has to be of the same type as the old one, since the registered trampoline is
+    /// specific to the hook type. To switch between different closures, use a function pointer
+    /// type for `F`.
+    pub fn set_hook(self: Pin<&mut Self>, hook: F) {
+        // the hook isn't pinned, so replacing it doesn't move the handler
+        unsafe { self.get_unchecked_mut().hook = hook };
+    }
+
+    /// Replace the writer, returning the old one, the handler stays registered if it was
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handler was detached, there is no writer to replace then.
+    pub fn replace_writer(self: Pin<&mut Self>, writer: W) -> W {
+        // the writer is swapped in place so there is no moment where it's uninitialized
+        unsafe { core::mem::replace(&mut **self.get_unchecked_mut(), writer) }
+    }
+
+    /// Shared access to the writer through a pinned reference
+    pub fn writer(self: Pin<&Self>) -> &W {
+        self.get_ref()
+    }
+
+    /// Whether the handler currently holds a writer
+    ///
+    /// This is false after [`Registration::detach`] took the writer out, until it's given back
+    /// with [`reattach`](PanicHandler::reattach).
+    pub fn writer_is_initialized(&self) -> bool {
+        self.initialized
     }
 }
 
-// TODO: what happens we if have multiple of these?
-impl<W: Write, F: FnMut(&mut W, &PanicInfo)> Drop for Pin<&mut PanicHandler<W, F>> {
+/// Capturing the panic message into a `heapless::String`
+///
+/// Output that doesn't fit in the string's capacity is dropped.
+#[cfg(feature = "heapless")]
+impl<const N: usize, F: FnMut(&mut heapless::String<N>, &PanicInfo), S>
+    PanicHandler<heapless::String<N>, F, S>
+{
+    /// The panic message captured so far
+    pub fn peek_message(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<W: Write, F: FnMut(&mut W, &PanicInfo), S> Drop for PanicHandler<W, F, S> {
     fn drop(&mut self) {
-        unsafe {
-            PANIC_HANDLER_GETTER = None;
-            PANIC_HANDLER = null_mut();
+        // the handler might have been detached already or never registered at all
+        unsafe { registry::remove(&mut self.node) };
+
+        // a detached writer was moved out already
+        if self.initialized {
+            self.initialized = false;
+            unsafe { self.writer.as_mut_ptr().drop_in_place() };
         }
     }
 }
 
-impl<W: Write, F: FnMut(&mut W, &PanicInfo)> core::ops::Deref for PanicHandler<W, F> {
+/// Guard for a registered panic handler, the handler is deregistered when this is dropped
+///
+/// The guard holds the handler in the [`Registered`] state and mutably borrows it, so the handler
+/// can't be registered again, borrowed or moved while the guard exists. Detaching is only possible
+/// through the guard. See [`PanicHandler::register`] for how multiple registrations interact.
+#[must_use = "the handler is deregistered when the registration is dropped"]
+pub struct Registration<'a, W: Write, F: FnMut(&mut W, &PanicInfo)> {
+    handler: NonNull<PanicHandler<W, F, Registered>>,
+    _borrow: PhantomData<&'a mut PanicHandler<W, F>>,
+}
+
+impl<'a, W: Write, F: FnMut(&mut W, &PanicInfo)> Registration<'a, W, F> {
+    /// Replace the hook of the registered handler, see [`PanicHandler::set_hook`]
+    pub fn set_hook(&mut self, hook: F) {
+        unsafe { self.handler.as_mut().hook = hook };
+    }
+
+    /// Replace the writer of the registered handler, see [`PanicHandler::replace_writer`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handler was detached before it was registered again.
+    pub fn replace_writer(&mut self, writer: W) -> W {
+        core::mem::replace(&mut **self, writer)
+    }
+
+    /// Deregister and detach the handler, returning the writer
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handler was detached before it was registered.
+    pub fn detach(self) -> W {
+        PanicHandler::detach(self.into_pin())
+    }
+
+    /// Deregister and detach the handler, returning the writer or `None` if the handler was
+    /// detached before it was registered, see [`PanicHandler::try_detach`]
+    pub fn try_detach(self) -> Option<W> {
+        PanicHandler::try_detach(self.into_pin())
+    }
+
+    /// Deregister and detach the handler, transforming the writer, see
+    /// [`PanicHandler::map_writer`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handler was detached before it was registered.
+    pub fn map_writer<U>(self, f: impl FnOnce(W) -> U) -> U {
+        PanicHandler::map_writer(self.into_pin(), f)
+    }
+
+    /// The registered handler, without deregistering it when the guard is gone
+    fn into_pin(self) -> Pin<&'a mut PanicHandler<W, F, Registered>> {
+        let handler = self.handler;
+        // the handler is deregistered by detaching it, so the guard doesn't have to
+        core::mem::forget(self);
+        // safe because the handler was pinned when it was registered
+        unsafe { Pin::new_unchecked(&mut *handler.as_ptr()) }
+    }
+}
+
+impl<'a, W: Write, C, F: FnMut(&mut ContextWriter<W, C>, &PanicInfo)>
+    Registration<'a, ContextWriter<W, C>, F>
+{
+    /// Deregister and detach a handler created with
+    /// [`new_with_ctx`](PanicHandler::new_with_ctx), returning the writer and the context
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handler was detached before it was registered.
+    pub fn detach_with_ctx(self) -> (W, C) {
+        self.map_writer(ContextWriter::into_inner)
+    }
+}
+
+impl<'a, W: Write, F: FnMut(&mut W, &PanicInfo)> Drop for Registration<'a, W, F> {
+    fn drop(&mut self) {
+        unsafe { registry::remove(core::ptr::addr_of_mut!((*self.handler.as_ptr()).node)) };
+    }
+}
+
+impl<'a, W: Write, F: FnMut(&mut W, &PanicInfo)> core::ops::Deref for Registration<'a, W, F> {
+    type Target = W;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { self.handler.as_ref() }
+    }
+}
+
+impl<'a, W: Write, F: FnMut(&mut W, &PanicInfo)> core::ops::DerefMut for Registration<'a, W, F> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { self.handler.as_mut() }
+    }
+}
+
+impl<W: Write, F: FnMut(&mut W, &PanicInfo), S> core::ops::Deref for PanicHandler<W, F, S> {
     type Target = W;
 
     fn deref(&self) -> &Self::Target {
-        // safe because self.writer is only uninit during drop
+        assert!(self.initialized, "panic handler is detached");
+        // safe because the writer is initialized until it's detached
         unsafe { &*self.writer.as_ptr() }
     }
 }
 
-impl<W: Write, F: FnMut(&mut W, &PanicInfo)> core::ops::DerefMut for PanicHandler<W, F> {
+impl<W: Write, F: FnMut(&mut W, &PanicInfo), S> core::ops::DerefMut for PanicHandler<W, F, S> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        // safe because self.writer is only uninit during drop
+        assert!(self.initialized, "panic handler is detached");
+        // safe because the writer is initialized until it's detached
         unsafe { &mut *self.writer.as_mut_ptr() }
     }
 }
 
+/// A step of writing the panic that depends on the writer or hook type
+#[derive(Clone, Copy)]
+enum Step<'a> {
+    FramingStart,
+    Headline(&'a PanicInfo<'a>),
+    Hook(&'a PanicInfo<'a>),
+    Context,
+    DeviceId,
+    Field(usize),
+    FramingEnd,
+    Finish,
+    Flush,
+    PollFlush,
+}
+
+/// The part of writing the panic that depends on the writer and hook types of a handler,
+/// everything else is done by [`write_panic_dyn`]
+trait Steps {
+    fn options(&mut self) -> &mut CommonOptions;
+
+    /// The writer, or `None` if the handler is detached
+    fn writer(&mut self) -> Option<&mut dyn Write>;
+
+    /// The key of the field at `index`, if there is one
+    fn field_key(&self, index: usize) -> Option<&'static str>;
+
+    /// Run a step, returns whether the handler has anything set up for it
+    fn run(&mut self, step: Step) -> bool;
+}
+
+impl<W: Write, F: FnMut(&mut W, &PanicInfo), S> Steps for PanicHandler<W, F, S> {
+    fn options(&mut self) -> &mut CommonOptions {
+        &mut self.options.common
+    }
+
+    fn writer(&mut self) -> Option<&mut dyn Write> {
+        // safe because the writer is initialized
+        self.initialized
+            .then(|| unsafe { &mut *self.writer.as_mut_ptr() as &mut dyn Write })
+    }
+
+    fn field_key(&self, index: usize) -> Option<&'static str> {
+        self.options.fields[index].map(|(key, _)| key)
+    }
+
+    fn run(&mut self, step: Step) -> bool {
+        // a detached handler can be registered again, but has nothing to write to
+        if !self.initialized {
+            return false;
+        }
+        // safe because the writer is initialized
+        let writer: &mut W = unsafe { &mut *self.writer.as_mut_ptr() };
+        let options = &self.options;
+
+        let callback = match step {
+            Step::Hook(info) => {
+                (self.hook)(writer, info);
+                return true;
+            }
+            Step::PollFlush => {
+                #[cfg(feature = "nb")]
+                if let Some(poll_flush) = options.poll_flush {
+                    poll_flush.wait(writer);
+                    return true;
+                }
+                return false;
+            }
+            Step::Headline(info) => {
+                return match options.headline {
+                    Some(headline) => {
+                        headline(writer, info);
+                        true
+                    }
+                    None => false,
+                };
+            }
+            Step::FramingStart => options.framing.map(|(start, _)| start),
+            Step::FramingEnd => options.framing.map(|(_, end)| end),
+            Step::Context => options.context,
+            Step::DeviceId => options.device_id,
+            Step::Field(index) => options.fields[index].map(|(_, value)| value),
+            Step::Finish => options.finish,
+            Step::Flush => options.flush,
+        };
+        match callback {
+            Some(callback) => {
+                callback(writer);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn write_str(handler: &mut dyn Steps, s: &str) {
+    if let Some(writer) = handler.writer() {
+        let _ = writer.write_str(s);
+    }
+}
+
+fn write_fmt(handler: &mut dyn Steps, args: core::fmt::Arguments) {
+    if let Some(writer) = handler.writer() {
+        let _ = writer.write_fmt(args);
+    }
+}
+
+/// Write the panic message, returns the handler specific post-panic action if one is set
+///
+/// This is shared by all handlers, only the steps that need the writer or hook type go through
+/// the handler's [`Steps`], which keeps the code instantiated per writer type small.
+#[inline(never)]
+fn write_panic_dyn(handler: &mut dyn Steps, info: &PanicInfo) -> Option<Outcome> {
+    // before anything else, writing the panic can take long enough for the watchdog to fire
+    if let Some(disable_watchdog) = handler.options().disable_watchdog {
+        disable_watchdog();
+    }
+
+    // side effects run first so they happen even if writing the message fails
+    if let Some(side_effect) = handler.options().side_effect {
+        side_effect(info);
+    }
+
+    // a detached handler can be registered again, but has nothing to write to
+    handler.writer()?;
+
+    if panic_depth() > 1 {
+        write_str(handler, "nested panic: ");
+    }
+
+    // the summary is short, write it before the full message in case a slow writer doesn't
+    // get to finish
+    if let Some(summary) = &mut handler.options().summary {
+        write_summary(*summary, info);
+    }
+
+    handler.run(Step::FramingStart);
+
+    if let Some(uptime) = handler.options().stats_header {
+        write_fmt(handler, format_args!("#{} @{}\n", panic_count(), uptime()));
+    }
+
+    // the headline is short too, it gets the location out before the full message
+    if handler.run(Step::Headline(info)) {
+        write_str(handler, "\n");
+    }
+
+    if let Some(prefix) = handler.options().prefix {
+        write_str(handler, prefix);
+    }
+
+    if let Some(banner) = handler.options().banner {
+        write_str(handler, banner);
+    }
+
+    if let Some(clock) = handler.options().clock {
+        write_fmt(handler, format_args!("[{}] ", clock()));
+    }
+
+    handler.run(Step::Hook(info));
+    handler.run(Step::Context);
+    handler.run(Step::DeviceId);
+
+    for index in 0..MAX_FIELDS {
+        if let Some(key) = handler.field_key(index) {
+            write_fmt(handler, format_args!(" {}=", key));
+            handler.run(Step::Field(index));
+        }
+    }
+
+    if let Some(line_ending) = handler.options().line_ending {
+        write_str(handler, line_ending);
+    }
+
+    if let Some(suffix) = handler.options().suffix {
+        write_str(handler, suffix);
+    }
+
+    handler.run(Step::FramingEnd);
+    handler.run(Step::Finish);
+    handler.run(Step::Flush);
+
+    // the decision of a deciding hook overrides the handler's fixed action
+    let options = handler.options();
+    let action = action::take_decision().or(options.action)?;
+    Some(Outcome {
+        action,
+        #[cfg(feature = "cortex-m")]
+        reset_delay: options.reset_delay,
+    })
+}
+
+/// Wait for the non-blocking flush of a handler, see [`wait_for_flushes`]
+#[inline(never)]
+fn poll_flush_dyn(handler: &mut dyn Steps) {
+    handler.run(Step::PollFlush);
+}
+
+/// Run only the finish and flush steps, after the hook or writer panicked
+#[inline(never)]
+fn finish_output_dyn(handler: &mut dyn Steps) {
+    handler.run(Step::Finish);
+    handler.run(Step::Flush);
+}
+
+/// Write only the location of the panic, shared by all handler types instead of instantiated
+/// for every writer
+#[inline(never)]
+fn write_summary(summary: &mut dyn Write, info: &PanicInfo) {
+    let _ = match info.location() {
+        Some(location) => write!(summary, "{}:{}", location.file(), location.line()),
+        None => summary.write_str("<unknown>"),
+    };
+}
+
+/// Write the panic message using the registered handlers and return the post-panic action
+#[cold]
+fn run_handlers(info: &PanicInfo) -> Outcome {
+    // no read-modify-write, not all targets support atomic increments
+    let count = PANIC_COUNT.load(Ordering::Acquire);
+    PANIC_COUNT.store(count.wrapping_add(1), Ordering::Release);
+
+    let depth = DEPTH.load(Ordering::Acquire);
+    DEPTH.store(depth.saturating_add(1), Ordering::Release);
+
+    // nested panics (e.g. from the writer) past the limit skip straight to the post-panic action
+    // instead of re-entering the handler forever
+    let max_depth = MAX_DEPTH.load(Ordering::Acquire);
+    if depth < max_depth {
+        if depth == 0 && !is_registered() {
+            early::write_panic(info);
+        }
+
+        let mut outcome = None;
+        registry::for_each(|handler, trampoline| {
+            let handler_outcome = call_trampoline(trampoline, handler, info);
+            outcome = outcome.or(handler_outcome);
+        });
+        if let Some(outcome) = outcome {
+            return outcome;
+        }
+    } else if depth == max_depth {
+        // the hook or writer panicked, still try to leave the output in a usable state, e.g. by
+        // resetting the terminal color or flushing buffered output
+        registry::for_each_finisher(|handler, finisher| unsafe { finisher(handler) });
+    }
+    action::action().into()
+}
+
+#[cfg(not(feature = "critical-section"))]
+fn call_trampoline(trampoline: Trampoline, handler: *mut (), info: &PanicInfo) -> Option<Outcome> {
+    unsafe { trampoline(handler, info) }
+}
+
+/// Write the panic message with interrupts disabled so it can't be interleaved with other output
+#[cfg(feature = "critical-section")]
+fn call_trampoline(trampoline: Trampoline, handler: *mut (), info: &PanicInfo) -> Option<Outcome> {
+    critical_section::with(|_| unsafe { trampoline(handler, info) })
+}
+
+/// Poll the non-blocking flushes of all handlers until they complete or run out of polls, the
+/// last thing before the post-panic action
+fn wait_for_flushes() {
+    registry::for_each_poller(|handler, poller| unsafe { poller(handler) });
+}
+
+/// Write the panic message using the registered handlers and perform the post-panic action
+///
+/// This is what the `#[panic_handler]` defined by this crate does. With the default
+/// `panic-handler` feature disabled no `#[panic_handler]` is defined, which allows libraries to
+/// depend on this crate, the binary can then call this from its own handler:
+///
+/// ```ignore
+/// #[panic_handler]
+/// fn panic(info: &PanicInfo) -> ! {
+///     panic_write::handle_panic(info)
+/// }
+/// ```
+///
+/// When no handler is registered the panic is written to the writer set by
+/// [`set_early_writer`], or not at all if there is none, and the action set by [`set_action`]
+/// is performed.
+///
+/// A `PanicInfo` can't be constructed outside of a panic handler, so a fault handler that wants
+/// to report through the registered handlers can simply `panic!` with a description of the
+/// fault, which ends up here with the same writer, hook and post-panic action.
+#[cold]
+pub fn handle_panic(info: &PanicInfo) -> ! {
+    let outcome = run_handlers(info);
+    wait_for_flushes();
+    if let Some(resume) = action::resume() {
+        // the program keeps running, so the next panic isn't nested in this one
+        DEPTH.store(0, Ordering::Release);
+        resume();
+    }
+    action::halt(outcome)
+}
+
+// tests and the `simulate` feature use the panic handler from std
+#[cfg(all(feature = "panic-handler", not(any(test, feature = "simulate"))))]
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    unsafe {
-        if let Some(trampoline) = PANIC_HANDLER_GETTER {
-            trampoline(PANIC_HANDLER, info);
+    // before any call, which would overwrite the link register
+    #[cfg(feature = "cortex-m")]
+    format::record_entry();
+    handle_panic(info)
+}
+
+/// Run the registered handler as if a panic happened and return the post-panic action instead of
+/// halting
+///
+/// This is only available with the `simulate` feature, which doesn't define a `#[panic_handler]`
+/// so the crate can be used in tests on the host. The handler can be run any number of times.
+#[cfg(any(test, feature = "simulate"))]
+pub fn simulate_panic(info: &PanicInfo) -> PanicAction {
+    let action = run_handlers(info).action;
+    wait_for_flushes();
+    DEPTH.store(0, Ordering::Release);
+    action
+}
+
+/// Run the registered handler as if a panic with `message` happened at `location`, see
+/// [`simulate_panic`]
+///
+/// ```
+/// # let mut handler = core::pin::pin!(panic_write::PanicHandler::new(String::new()));
+/// # let registration = handler.as_mut().register();
+/// panic_write::simulate_panic_with(format_args!("boom"), None);
+/// assert_eq!(*registration, "panicked at :\nboom");
+/// ```
+#[cfg(any(test, feature = "simulate"))]
+pub fn simulate_panic_with(
+    message: core::fmt::Arguments,
+    location: Option<&core::panic::Location>,
+) -> PanicAction {
+    simulate_panic(&PanicInfo::new(message, location))
+}
+
+/// Reset all global state to how it is at startup, to isolate tests from each other
+///
+/// All handlers are deregistered, the early writer is unset, the panic count and depth are
+/// cleared and everything set with functions like [`set_action`] is back at the default. Handlers
+/// that were registered can still be dropped or registered again afterwards. Messages stored by a
+/// [`PersistentWriter`] are kept, like they are across a reset.
+#[cfg(any(test, feature = "simulate"))]
+pub fn reset_globals() {
+    registry::clear();
+    early::clear();
+    action::reset();
+    DEPTH.store(0, Ordering::Release);
+    MAX_DEPTH.store(1, Ordering::Release);
+    PANIC_COUNT.store(0, Ordering::Release);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::AtomicBool;
+    use std::sync::{Mutex, MutexGuard};
+
+    /// Registering a handler changes global state, so tests that do run one at a time, starting
+    /// from a clean state
+    pub(crate) fn globals() -> MutexGuard<'static, ()> {
+        static GLOBALS: Mutex<()> = Mutex::new(());
+        let guard = GLOBALS
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        reset_globals();
+        guard
+    }
+
+    /// Critical section for the tests, a lock that can be taken again by the thread holding it
+    ///
+    /// The `std` implementation from `critical-section` can't be used, it would bring the panic
+    /// handler of `std` into the crate.
+    #[cfg(feature = "critical-section")]
+    struct TestCriticalSection;
+
+    #[cfg(feature = "critical-section")]
+    critical_section::set_impl!(TestCriticalSection);
+
+    #[cfg(feature = "critical-section")]
+    unsafe impl critical_section::Impl for TestCriticalSection {
+        unsafe fn acquire() {
+            if CRITICAL_SECTION_DEPTH.get() == 0 {
+                while CRITICAL_SECTION_LOCKED
+                    .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                    .is_err()
+                {
+                    std::thread::yield_now();
+                }
+            }
+            CRITICAL_SECTION_DEPTH.set(CRITICAL_SECTION_DEPTH.get() + 1);
+        }
+
+        unsafe fn release(_: ()) {
+            CRITICAL_SECTION_DEPTH.set(CRITICAL_SECTION_DEPTH.get() - 1);
+            if CRITICAL_SECTION_DEPTH.get() == 0 {
+                CRITICAL_SECTION_LOCKED.store(false, Ordering::Release);
+            }
         }
     }
-    loop {}
+
+    #[cfg(feature = "critical-section")]
+    static CRITICAL_SECTION_LOCKED: AtomicBool = AtomicBool::new(false);
+
+    #[cfg(feature = "critical-section")]
+    std::thread_local! {
+        static CRITICAL_SECTION_DEPTH: core::cell::Cell<usize> = const { core::cell::Cell::new(0) };
+    }
+
+    #[test]
+    fn dropping_the_registration_deregisters() {
+        let _globals = globals();
+        let mut handler = core::pin::pin!(PanicHandler::new(String::new()));
+        {
+            let _registration = handler.as_mut().register();
+            assert!(is_registered());
+        }
+        assert!(!is_registered());
+        // the handler can be registered again afterwards
+        let _registration = handler.register();
+        assert!(is_registered());
+    }
+
+    #[test]
+    fn dropping_the_handler_deregisters() {
+        let _globals = globals();
+        {
+            let handler = core::pin::pin!(PanicHandler::new(String::new()));
+            // without the guard, only dropping the handler deregisters it
+            core::mem::forget(handler.register());
+            assert!(is_registered());
+        }
+        assert!(!is_registered());
+        // doesn't reach the dropped handler
+        simulate_panic_with(format_args!("boom"), None);
+    }
+
+    /// Records what was written when it was flushed
+    #[derive(Default)]
+    struct MockUart {
+        written: String,
+        flushed: Option<String>,
+    }
+
+    impl Write for MockUart {
+        fn write_str(&mut self, s: &str) -> core::fmt::Result {
+            self.written.push_str(s);
+            Ok(())
+        }
+    }
+
+    impl Flush for MockUart {
+        fn flush(&mut self) {
+            self.flushed = Some(self.written.clone());
+        }
+    }
+
+    #[test]
+    fn flushes_after_the_message() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_flush(
+            MockUart::default(),
+            |uart: &mut MockUart, info: &PanicInfo| {
+                let _ = write!(uart, "{}", info.message());
+            },
+            Flush::flush
+        ));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(registration.flushed.as_deref(), Some("boom"));
+    }
+
+    #[test]
+    #[cfg(feature = "cortex-m")]
+    fn uart_reset_flushes_and_resets_after_its_own_delay() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::uart_reset_with_delay(MockUart::default(), 7));
+        let registration = handler.register();
+        let outcome = run_handlers(&PanicInfo::new(format_args!("boom"), None));
+        assert_eq!(outcome.action, PanicAction::Reset);
+        assert_eq!(outcome.reset_delay, Some(7));
+        assert_eq!(registration.flushed.as_deref(), Some("panicked at :\nboom"));
+    }
+
+    /// Panics in the middle of writing the message, like a driver hitting an `unwrap`
+    #[derive(Default)]
+    struct PanickingWriter {
+        written: String,
+        /// The action the nested panic returned
+        nested: Option<PanicAction>,
+        flushes: usize,
+        /// The number of flushes once the nested panic returned
+        flushes_after_nested: usize,
+    }
+
+    impl Flush for PanickingWriter {
+        fn flush(&mut self) {
+            self.flushes += 1;
+        }
+    }
+
+    impl Write for PanickingWriter {
+        fn write_str(&mut self, s: &str) -> core::fmt::Result {
+            self.written.push_str(s);
+            // the panic handler is entered again, like for a panic in a `#[panic_handler]`
+            if self.nested.is_none() {
+                self.nested =
+                    Some(run_handlers(&PanicInfo::new(format_args!("nested"), None)).action);
+                self.flushes_after_nested = self.flushes;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn panic_in_the_writer_skips_the_handler() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_hook(
+            PanickingWriter::default(),
+            |out: &mut PanickingWriter, info: &PanicInfo| {
+                let _ = write!(out, "{}", info.message());
+            }
+        ));
+        let registration = handler.register();
+        assert_eq!(
+            simulate_panic_with(format_args!("boom"), None),
+            PanicAction::SpinLoop
+        );
+        // the nested panic didn't write anything, but still got the post-panic action
+        assert_eq!(registration.written, "boom");
+        assert_eq!(registration.nested, Some(PanicAction::SpinLoop));
+    }
+
+    #[test]
+    fn nested_panic_still_flushes_the_output() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_flush(
+            PanickingWriter::default(),
+            |out: &mut PanickingWriter, info: &PanicInfo| {
+                let _ = write!(out, "{}", info.message());
+            },
+            Flush::flush
+        ));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(registration.flushes_after_nested, 1);
+        // the outer handler returned as well here, so it flushed again
+        assert_eq!(registration.flushes, 2);
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn captures_into_a_heapless_string() {
+        let _globals = globals();
+        let mut handler = core::pin::pin!(PanicHandler::new(heapless::String::<32>::new()));
+        let registration = handler.as_mut().register();
+        simulate_panic_with(format_args!("boom {}", 42), None);
+        drop(registration);
+        assert_eq!(handler.peek_message(), "panicked at :\nboom 42");
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn drops_what_doesnt_fit_the_heapless_string() {
+        let _globals = globals();
+        let mut handler = core::pin::pin!(PanicHandler::new(heapless::String::<16>::new()));
+        let registration = handler.as_mut().register();
+        simulate_panic_with(format_args!("boom {}", 42), None);
+        drop(registration);
+        assert_eq!(handler.peek_message(), "panicked at :\n");
+    }
+
+    #[test]
+    fn markers_surround_the_output() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_markers(
+            String::new(),
+            "<<PANIC\n",
+            "\nPANIC>>"
+        ));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+
+        let mut hook_output = String::new();
+        default_hook(
+            &mut hook_output,
+            &PanicInfo::new(format_args!("boom"), None),
+        );
+        assert_eq!(*registration, format!("<<PANIC\n{}\nPANIC>>", hook_output));
+    }
+
+    fn message_hook(out: &mut String, info: &PanicInfo) {
+        let _ = write!(out, "{}", info.message());
+    }
+
+    fn location_hook(out: &mut String, info: &PanicInfo) {
+        let _ = write!(out, "{}", info.location().unwrap());
+    }
+
+    #[test]
+    fn hook_can_be_replaced_while_registered() {
+        let _globals = globals();
+        let location = core::panic::Location::caller();
+        let hook: fn(&mut String, &PanicInfo) = message_hook;
+        let mut handler = core::pin::pin!(PanicHandler::new_with_hook(String::new(), hook));
+        let mut registration = handler.as_mut().register();
+        simulate_panic_with(format_args!("boom"), Some(location));
+        registration.set_hook(location_hook);
+        simulate_panic_with(format_args!("boom"), Some(location));
+        drop(registration);
+
+        handler.as_mut().set_hook(message_hook);
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), Some(location));
+        assert_eq!(*registration, format!("boom{}boom", location));
+    }
+
+    #[test]
+    fn registration_is_reported() {
+        let _globals = globals();
+        assert!(!is_registered());
+        assert_eq!(current_writer_addr(), None);
+
+        let mut handler = core::pin::pin!(PanicHandler::new(String::new()));
+        let writer = NonNull::from(&**handler).cast::<()>();
+        let registration = handler.as_mut().register();
+        assert!(is_registered());
+        assert_eq!(current_writer_addr(), Some(writer));
+
+        drop(registration);
+        assert!(!is_registered());
+        assert_eq!(current_writer_addr(), None);
+    }
+
+    #[test]
+    fn timestamp_comes_before_the_message() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_clock(
+            String::new(),
+            message_hook as fn(&mut String, &PanicInfo),
+            || 1234
+        ));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(*registration, "[1234] boom");
+    }
+
+    #[test]
+    fn writes_through_a_borrowed_writer() {
+        let _globals = globals();
+        let mut out = String::new();
+        {
+            let handler = core::pin::pin!(PanicHandler::new_borrowed(&mut out));
+            let _registration = handler.register();
+            simulate_panic_with(format_args!("boom"), None);
+        }
+        assert!(!is_registered());
+        assert_eq!(out, "panicked at :\nboom");
+    }
+
+    #[cfg(feature = "critical-section")]
+    #[test]
+    fn writes_inside_a_critical_section() {
+        use std::sync::Arc;
+        use std::thread::{self, JoinHandle};
+        use std::time::Duration;
+
+        /// Starts a thread that enters a critical section while the message is written
+        #[derive(Default)]
+        struct Contender {
+            thread: Option<JoinHandle<()>>,
+            entered: Arc<AtomicBool>,
+            entered_while_writing: bool,
+        }
+
+        impl Write for Contender {
+            fn write_str(&mut self, _: &str) -> core::fmt::Result {
+                if self.thread.is_none() {
+                    let entered = self.entered.clone();
+                    self.thread = Some(thread::spawn(move || {
+                        critical_section::with(|_| entered.store(true, Ordering::Release))
+                    }));
+                    thread::sleep(Duration::from_millis(20));
+                    self.entered_while_writing = self.entered.load(Ordering::Acquire);
+                }
+                Ok(())
+            }
+        }
+
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new(Contender::default()));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        let contender = registration.detach();
+        contender.thread.unwrap().join().unwrap();
+        assert!(!contender.entered_while_writing);
+        assert!(contender.entered.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn all_handlers_run_most_recent_first() {
+        use core::sync::atomic::AtomicUsize;
+
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+        fn numbering_hook(out: &mut String, _info: &PanicInfo) {
+            let _ = write!(out, "{}", CALLS.load(Ordering::Relaxed));
+            CALLS.store(CALLS.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+        }
+
+        let _globals = globals();
+        let first = core::pin::pin!(PanicHandlerBuilder::new()
+            .hook(numbering_hook)
+            .action(PanicAction::Wfi)
+            .build(String::new()));
+        let second = core::pin::pin!(PanicHandler::new_with_hook(String::new(), numbering_hook));
+        let first = first.register();
+        let second = second.register();
+
+        // the second handler has no action, so the one of the first is used
+        assert_eq!(
+            simulate_panic_with(format_args!("boom"), None),
+            PanicAction::Wfi
+        );
+        assert_eq!(*second, "0");
+        assert_eq!(*first, "1");
+    }
+
+    #[test]
+    fn message_str_is_only_set_for_plain_messages() {
+        let value = std::hint::black_box(42);
+        assert_eq!(
+            message_str(&PanicInfo::new(format_args!("boom"), None)),
+            Some("boom")
+        );
+        assert_eq!(
+            message_str(&PanicInfo::new(format_args!("boom {}", value), None)),
+            None
+        );
+    }
+
+    #[test]
+    fn hooks_can_tell_static_from_formatted_messages() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_hook(
+            String::new(),
+            |out: &mut String, info: &PanicInfo| {
+                let kind = if message_is_static(info) {
+                    "static "
+                } else {
+                    "formatted "
+                };
+                out.push_str(kind);
+            }
+        ));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        let value = std::hint::black_box(42);
+        simulate_panic_with(format_args!("boom {}", value), None);
+        assert_eq!(*registration, "static formatted ");
+    }
+
+    #[test]
+    fn side_effect_runs_before_the_message() {
+        static SIDE_EFFECT_RAN: AtomicBool = AtomicBool::new(false);
+
+        /// Fails every write, after recording whether the side effect already ran
+        #[derive(Default)]
+        struct FailingWriter {
+            side_effect_ran: Option<bool>,
+        }
+
+        impl Write for FailingWriter {
+            fn write_str(&mut self, _: &str) -> core::fmt::Result {
+                self.side_effect_ran
+                    .get_or_insert(SIDE_EFFECT_RAN.load(Ordering::Acquire));
+                Err(core::fmt::Error)
+            }
+        }
+
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_side_effect(
+            FailingWriter::default(),
+            default_hook,
+            |_| SIDE_EFFECT_RAN.store(true, Ordering::Release)
+        ));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(registration.side_effect_ran, Some(true));
+    }
+
+    #[test]
+    fn replaced_writer_gets_the_panic() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new(String::from("old")));
+        let mut registration = handler.register();
+        assert_eq!(registration.replace_writer(String::new()), "old");
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(*registration, "panicked at :\nboom");
+    }
+
+    #[test]
+    fn detaching_twice_returns_none() {
+        let _globals = globals();
+        let mut handler = core::pin::pin!(PanicHandler::new(String::from("boom")));
+        assert_eq!(
+            handler.as_mut().register().try_detach().as_deref(),
+            Some("boom")
+        );
+        assert_eq!(handler.register().try_detach(), None);
+    }
+
+    #[test]
+    fn writer_is_readable_through_a_shared_pin() {
+        let _globals = globals();
+        let mut handler = core::pin::pin!(PanicHandler::new(String::new()));
+        let registration = handler.as_mut().register();
+        simulate_panic_with(format_args!("boom"), None);
+        drop(registration);
+        assert_eq!(handler.as_ref().writer(), "panicked at :\nboom");
+    }
+
+    #[test]
+    fn every_panic_is_counted() {
+        let _globals = globals();
+        let count = panic_count();
+        simulate_panic_with(format_args!("boom"), None);
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(panic_count(), count + 2);
+    }
+
+    #[test]
+    fn map_writer_transforms_the_captured_message() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new(String::new()));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        let bytes = registration.map_writer(String::into_bytes);
+        assert_eq!(bytes, b"panicked at :\nboom");
+        assert!(!is_registered());
+    }
+
+    // run with `--features hook-minimal`, `--features location-only` and `--features hook-message`
+    // to cover all hooks
+    #[test]
+    fn default_hook_follows_the_features() {
+        let location = core::panic::Location::caller();
+        let mut out = String::new();
+        default_hook(
+            &mut out,
+            &PanicInfo::new(format_args!("boom"), Some(location)),
+        );
+
+        #[cfg(feature = "hook-minimal")]
+        let expected = format!("panicked at {}", location);
+        #[cfg(all(feature = "location-only", not(feature = "hook-minimal")))]
+        let expected = location.to_string();
+        #[cfg(all(
+            feature = "hook-message",
+            not(any(feature = "hook-minimal", feature = "location-only"))
+        ))]
+        let expected = "boom";
+        #[cfg(not(any(
+            feature = "hook-minimal",
+            feature = "hook-message",
+            feature = "location-only"
+        )))]
+        let expected = format!("panicked at {}:\nboom", location);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    #[cfg(all(feature = "location-only", not(feature = "hook-minimal")))]
+    fn location_only_leaves_out_the_message() {
+        let _globals = globals();
+        let location = core::panic::Location::caller();
+        let handler = core::pin::pin!(PanicHandler::new(String::new()));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), Some(location));
+        assert_eq!(registration.detach(), location.to_string());
+    }
+
+    #[test]
+    fn once_hook_only_gets_the_first_panic() {
+        let _globals = globals();
+        let token = String::from("token");
+        let handler = core::pin::pin!(PanicHandler::new_with_once_hook(
+            String::new(),
+            move |out: &mut String, info: &PanicInfo| {
+                let _ = write!(out, "{} {}", token, info.message());
+            }
+        ));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("first"), None);
+        simulate_panic_with(format_args!("second"), None);
+        assert_eq!(*registration, "token first");
+    }
+
+    #[test]
+    fn try_register_fails_while_a_handler_is_registered() {
+        let _globals = globals();
+        let first = core::pin::pin!(PanicHandler::new_with_hook(String::new(), message_hook));
+        let first = first.try_register().unwrap();
+        {
+            let second = core::pin::pin!(PanicHandler::new_with_hook(String::new(), message_hook));
+            assert_eq!(second.try_register().err(), Some(AlreadyRegistered));
+        }
+        // dropping the second handler doesn't deregister the first
+        assert!(is_registered());
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(*first, "boom");
+        drop(first);
+        assert!(!is_registered());
+    }
+
+    #[test]
+    fn reregistered_detached_handler_only_runs_the_side_effect() {
+        static SIDE_EFFECT_RAN: AtomicBool = AtomicBool::new(false);
+
+        let _globals = globals();
+        let mut handler = core::pin::pin!(PanicHandler::new_with_side_effect(
+            String::new(),
+            default_hook,
+            |_| SIDE_EFFECT_RAN.store(true, Ordering::Release)
+        ));
+        assert_eq!(handler.as_mut().register().detach(), "");
+        let _registration = handler.register();
+        assert_eq!(
+            simulate_panic_with(format_args!("boom"), None),
+            PanicAction::SpinLoop
+        );
+        assert!(SIDE_EFFECT_RAN.load(Ordering::Acquire));
+    }
+
+    #[test]
+    #[should_panic(expected = "panic handler is detached")]
+    fn replacing_the_writer_of_a_detached_handler_panics() {
+        let _globals = globals();
+        let mut handler = core::pin::pin!(PanicHandler::new(String::new()));
+        handler.as_mut().register().detach();
+        handler.replace_writer(String::new());
+    }
+
+    #[test]
+    fn writer_can_be_set_up_before_pinning() {
+        let _globals = globals();
+        let mut handler = PanicHandler::new_with_hook(String::new(), message_hook);
+        handler.get_inner_unpinned().push_str("boot: ");
+        let handler = core::pin::pin!(handler);
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(*registration, "boot: boom");
+    }
+
+    #[test]
+    fn statically_registered_handler_can_be_detached() {
+        let _globals = globals();
+        let handler = Box::leak(Box::new(PanicHandler::new_with_hook(
+            String::new(),
+            message_hook as fn(&mut String, &PanicInfo),
+        )));
+        let mut handler = PanicHandler::register_static(handler);
+        simulate_panic_with(format_args!("boom"), None);
+        handler.as_mut().set_hook(location_hook);
+        assert_eq!(PanicHandler::detach(handler.as_mut()), "boom");
+        assert!(!is_registered());
+        assert_eq!(PanicHandler::try_detach(handler), None);
+    }
+
+    #[test]
+    fn dyn_handler_writes_through_the_trait_object() {
+        let _globals = globals();
+        let mut expected = String::new();
+        default_hook(&mut expected, &PanicInfo::new(format_args!("boom"), None));
+
+        let mut out = String::new();
+        {
+            let handler = core::pin::pin!(PanicHandler::new_dyn(&mut out));
+            let _registration = handler.register();
+            simulate_panic_with(format_args!("boom"), None);
+        }
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn with_handler_registers_for_the_scope() {
+        let _globals = globals();
+        let out = with_handler(String::new(), message_hook, || {
+            assert!(is_registered());
+            simulate_panic_with(format_args!("boom"), None);
+        });
+        assert!(!is_registered());
+        assert_eq!(out, "boom");
+    }
+
+    #[test]
+    fn detached_writer_can_be_reattached() {
+        let _globals = globals();
+        let mut handler = core::pin::pin!(PanicHandler::new_with_hook(String::new(), message_hook));
+        let mut writer = handler.as_mut().register().detach();
+        assert!(!is_registered());
+        writer.push_str("used elsewhere, ");
+
+        let registration = PanicHandler::reattach(handler.as_mut(), writer).unwrap();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(registration.detach(), "used elsewhere, boom");
+    }
+
+    #[test]
+    fn reattaching_a_handler_with_a_writer_fails() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new(String::from("old")));
+        assert_eq!(
+            PanicHandler::reattach(handler, String::from("new")).err(),
+            Some(String::from("new"))
+        );
+    }
+
+    #[test]
+    fn is_panicking_while_the_hook_runs() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_hook(
+            String::new(),
+            |out: &mut String, _info: &PanicInfo| {
+                let _ = write!(out, "{}", is_panicking());
+            }
+        ));
+        let registration = handler.register();
+        assert!(!is_panicking());
+        simulate_panic_with(format_args!("boom"), None);
+        assert!(!is_panicking());
+        assert_eq!(*registration, "true");
+    }
+
+    #[test]
+    fn captures_into_a_buffer() {
+        let _globals = globals();
+        let mut buffer = [0; 32];
+        let handler = core::pin::pin!(PanicHandler::capture_into(&mut buffer));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        let captured = registration.map_writer(SliceWriter::into_written);
+        assert_eq!(captured, b"panicked at :\nboom");
+    }
+
+    #[test]
+    #[cfg(feature = "cortex-m")]
+    fn deciding_hook_chooses_the_action() {
+        let _globals = globals();
+        set_action(PanicAction::Wfi);
+        let handler = core::pin::pin!(PanicHandler::new_with_deciding_hook(
+            String::new(),
+            |out: &mut String, info: &PanicInfo| {
+                let _ = write!(out, "{}", info.message());
+                if info.message().as_str() == Some("fatal") {
+                    PanicAction::Reset
+                } else {
+                    PanicAction::SpinLoop
+                }
+            }
+        ));
+        let _registration = handler.register();
+        assert_eq!(
+            simulate_panic_with(format_args!("fatal"), None),
+            PanicAction::Reset
+        );
+        assert_eq!(
+            simulate_panic_with(format_args!("recoverable"), None),
+            PanicAction::SpinLoop
+        );
+        set_action(PanicAction::SpinLoop);
+    }
+
+    #[test]
+    fn writer_is_not_initialized_while_detached() {
+        let _globals = globals();
+        let mut handler = core::pin::pin!(PanicHandler::new(String::new()));
+        assert!(handler.writer_is_initialized());
+        let writer = handler.as_mut().register().detach();
+        assert!(!handler.writer_is_initialized());
+        let registration = PanicHandler::reattach(handler.as_mut(), writer).unwrap();
+        simulate_panic_with(format_args!("boom"), None);
+        drop(registration);
+        assert!(handler.writer_is_initialized());
+    }
+
+    #[test]
+    fn try_register_fails_for_a_handler_of_another_type() {
+        let _globals = globals();
+        let first = core::pin::pin!(PanicHandler::new_with_hook(String::new(), message_hook));
+        let first = first.try_register().unwrap();
+        {
+            let second = core::pin::pin!(PanicHandler::new(writers::HexWriter::new(String::new())));
+            assert_eq!(second.try_register().err(), Some(AlreadyRegistered));
+        }
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(first.detach(), "boom");
+    }
+
+    #[test]
+    fn context_handler_detaches_into_the_writer_and_context() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_ctx(
+            String::new(),
+            0,
+            |out: &mut String, panics: &mut u32, info: &PanicInfo| {
+                *panics += 1;
+                let _ = write!(out, "{}", info.message());
+            }
+        ));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(registration.detach_with_ctx(), (String::from("boom"), 1));
+    }
+
+    #[test]
+    fn nested_panics_are_written_up_to_the_max_depth() {
+        let _globals = globals();
+        set_max_panic_depth(2);
+        let handler = core::pin::pin!(PanicHandler::new_with_hook(
+            PanickingWriter::default(),
+            |out: &mut PanickingWriter, info: &PanicInfo| {
+                let _ = write!(out, "{}", info.message());
+            }
+        ));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        set_max_panic_depth(1);
+        assert_eq!(registration.written, "boomnested panic: nested");
+        assert_eq!(panic_depth(), 0);
+    }
+
+    #[test]
+    fn reset_globals_forgets_registered_handlers() {
+        let _globals = globals();
+        set_action(PanicAction::Wfi);
+        let handler = core::pin::pin!(PanicHandler::new(String::new()));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        reset_globals();
+        assert!(!is_registered());
+        assert_eq!(panic_count(), 0);
+        assert_eq!(
+            simulate_panic_with(format_args!("again"), None),
+            PanicAction::SpinLoop
+        );
+        // the handler wasn't written to again, and can still be deregistered
+        assert_eq!(registration.detach(), "panicked at :\nboom");
+    }
+
+    #[test]
+    fn try_new_checks_the_capacity() {
+        let mut empty = [0; 0];
+        let handler = PanicHandler::try_new(SliceWriter::new(&mut empty));
+        assert_eq!(handler.err(), Some(HandlerError::NoCapacity));
+
+        // the marker needs room of its own
+        let mut small = [0; 3];
+        let handler = PanicHandler::try_new(SliceWriter::with_marker(&mut small, "..."));
+        assert_eq!(handler.err(), Some(HandlerError::NoCapacity));
+
+        let mut buffer = [0; 16];
+        let handler = PanicHandler::try_new(SliceWriter::with_marker(&mut buffer, "..."));
+        assert!(handler.is_ok());
+    }
+
+    #[test]
+    fn stats_header_counts_the_panics() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandlerBuilder::new()
+            .hook(message_hook)
+            .stats_header(|| 1234)
+            .build(String::new()));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        simulate_panic_with(format_args!("again"), None);
+        assert_eq!(registration.detach(), "#1 @1234\nboom#2 @1234\nagain");
+    }
 }
@@ -0,0 +1,53 @@
+/// Create a panic handler, pin it to the stack and register it in one step
+///
+/// The handler stays registered until the end of the enclosing scope, the named binding can be
+/// used in place of the writer in the meantime.
+///
+/// ```ignore
+/// use core::fmt::Write;
+///
+/// panic_write::install!(serial = uart);
+/// writeln!(serial, "starting app").ok();
+///
+/// // or with a custom hook
+/// panic_write::install!(serial = uart, panic_write::format::location_hook);
+/// ```
+#[macro_export]
+macro_rules! install {
+    ($name:ident = $writer:expr) => {
+        let handler = ::core::pin::pin!($crate::PanicHandler::new($writer));
+        #[allow(unused_mut)]
+        let mut $name = handler.register();
+    };
+    ($name:ident = $writer:expr, $hook:expr) => {
+        let handler = ::core::pin::pin!($crate::PanicHandler::new_with_hook($writer, $hook));
+        #[allow(unused_mut)]
+        let mut $name = handler.register();
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::globals;
+    use crate::{is_registered, simulate_panic_with};
+
+    #[test]
+    fn installs_until_the_end_of_the_scope() {
+        let _globals = globals();
+        {
+            crate::install!(out = String::new());
+            out.push_str("starting app: ");
+            simulate_panic_with(format_args!("boom"), None);
+            assert_eq!(*out, "starting app: panicked at :\nboom");
+        }
+        assert!(!is_registered());
+    }
+
+    #[test]
+    fn installs_with_a_hook() {
+        let _globals = globals();
+        crate::install!(out = String::new(), crate::format::location_hook);
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(*out, "PANIC <unknown> boom");
+    }
+}
@@ -0,0 +1,335 @@
+use core::cell::UnsafeCell;
+use core::fmt::{Result, Write};
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr::addr_of_mut;
+
+/// The number of bytes of the panic message that are kept by [`PersistentWriter`]
+pub const PERSISTENT_CAPACITY: usize = 256;
+
+const MAGIC: u32 = 0x5041_4e43;
+
+#[repr(C)]
+struct PersistentBuffer {
+    magic: u32,
+    len: u32,
+    truncated: u32,
+    data: [u8; PERSISTENT_CAPACITY],
+}
+
+// placed in a section that isn't zeroed on startup so the message survives a reset
+#[cfg(target_os = "none")]
+#[link_section = ".uninit.panic_write"]
+static mut BUFFER: MaybeUninit<PersistentBuffer> = MaybeUninit::uninit();
+
+// hosted programs start with a fresh buffer every run, like after a cold boot without garbage
+#[cfg(not(target_os = "none"))]
+static mut BUFFER: MaybeUninit<PersistentBuffer> = MaybeUninit::zeroed();
+
+fn buffer() -> *mut PersistentBuffer {
+    addr_of_mut!(BUFFER) as *mut PersistentBuffer
+}
+
+/// Writer that stores the panic message in RAM that isn't cleared on reset
+///
+/// On the next boot the message can be read back with [`last_panic`]. This requires the linker
+/// script to place `.uninit.*` sections in RAM that isn't initialized on startup, as the
+/// `cortex-m-rt` linker script does.
+///
+/// The stored message is only replaced once something is written, so a writer that's created at
+/// startup and never used, because the program didn't panic, leaves nothing behind for
+/// [`last_panic`]. At most [`PERSISTENT_CAPACITY`] bytes are stored, anything beyond that is
+/// dropped.
+pub struct PersistentWriter {
+    /// Whether the buffer was claimed for this writer's message by the first write
+    started: bool,
+    // all writers share the same buffer, don't allow moving a writer to another thread
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl PersistentWriter {
+    /// Create a writer, the previously stored message is cleared by the first write
+    ///
+    /// # Safety
+    ///
+    /// No message returned by [`last_panic`] can be used after the writer is written to.
+    pub unsafe fn new() -> Self {
+        PersistentWriter {
+            started: false,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Whether any output was dropped because the buffer was full
+    pub fn truncated(&self) -> bool {
+        self.started && unsafe { (*buffer()).truncated != 0 }
+    }
+}
+
+impl Write for PersistentWriter {
+    fn write_str(&mut self, s: &str) -> Result {
+        unsafe {
+            let buffer = buffer();
+            if !self.started {
+                self.started = true;
+                (*buffer).magic = MAGIC;
+                (*buffer).len = 0;
+                (*buffer).truncated = 0;
+            }
+            // the length is only ever set by us, but guard against it being corrupted anyway
+            let position = ((*buffer).len as usize).min(PERSISTENT_CAPACITY);
+            let len = s.len().min(PERSISTENT_CAPACITY - position);
+            let data = addr_of_mut!((*buffer).data) as *mut u8;
+            core::ptr::copy_nonoverlapping(s.as_ptr(), data.add(position), len);
+            (*buffer).len = (position + len) as u32;
+            if len < s.len() {
+                (*buffer).truncated = 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The panic message stored by a [`PersistentWriter`] before the last reset, if any
+///
+/// After a cold boot the buffer contains garbage, which is detected by checking for a magic value.
+pub fn last_panic() -> Option<&'static str> {
+    unsafe {
+        let buffer = buffer();
+        if (*buffer).magic != MAGIC || (*buffer).len as usize > PERSISTENT_CAPACITY {
+            return None;
+        }
+        let data = core::slice::from_raw_parts(
+            addr_of_mut!((*buffer).data) as *const u8,
+            (*buffer).len as usize,
+        );
+        Some(valid_prefix(data))
+    }
+}
+
+/// Truncation can cut a character in half, only return the valid part
+fn valid_prefix(data: &[u8]) -> &str {
+    match core::str::from_utf8(data) {
+        Ok(message) => message,
+        Err(e) => unsafe { core::str::from_utf8_unchecked(&data[..e.valid_up_to()]) },
+    }
+}
+
+const RING_MAGIC: u32 = 0x5257_4e47;
+
+#[repr(C)]
+struct Slot<const S: usize> {
+    valid: u32,
+    len: u32,
+    data: [u8; S],
+}
+
+#[repr(C)]
+struct Ring<const N: usize, const S: usize> {
+    magic: u32,
+    /// The slot the next panic is written to, which is also the oldest slot
+    head: u32,
+    slots: [Slot<S>; N],
+}
+
+/// Ring buffer of the last `N` panic messages, kept in RAM that isn't cleared on reset
+///
+/// Every message is stored in a slot of `S` bytes, longer messages are truncated. The ring has to
+/// be placed in a section that isn't initialized on startup by the user:
+///
+/// ```ignore
+/// #[link_section = ".uninit.crash_ring"]
+/// static CRASHES: CrashRing<4> = CrashRing::new();
+///
+/// for message in CRASHES.last_panics() {
+///     writeln!(serial, "previous crash: {}", message).ok();
+/// }
+///
+/// let handler = pin!(PanicHandler::new(unsafe { CRASHES.writer() }));
+/// ```
+///
+/// After a cold boot the ring contains garbage, which is detected by checking for a magic value.
+pub struct CrashRing<const N: usize, const S: usize = 128> {
+    ring: UnsafeCell<MaybeUninit<Ring<N, S>>>,
+}
+
+// all access goes through raw pointers and `writer` requires the caller to prevent overlapping use
+unsafe impl<const N: usize, const S: usize> Sync for CrashRing<N, S> {}
+
+impl<const N: usize, const S: usize> CrashRing<N, S> {
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        CrashRing {
+            ring: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    fn ring(&self) -> *mut Ring<N, S> {
+        self.ring.get() as *mut Ring<N, S>
+    }
+
+    fn is_valid(&self) -> bool {
+        unsafe { (*self.ring()).magic == RING_MAGIC }
+    }
+
+    /// The stored panic messages, from oldest to newest
+    pub fn last_panics(&self) -> impl Iterator<Item = &str> + '_ {
+        let (valid, head) = match (self.is_valid(), N) {
+            (true, 1..) => (true, unsafe { (*self.ring()).head as usize % N }),
+            _ => (false, 0),
+        };
+        (0..N)
+            .filter(move |_| valid)
+            .filter_map(move |i| unsafe { slot_message(self.slot((head + i) % N)) })
+    }
+
+    fn slot(&self, index: usize) -> *mut Slot<S> {
+        unsafe { addr_of_mut!((*self.ring()).slots[index]) }
+    }
+
+    /// Return a writer for the next slot, overwriting the oldest message if the ring is full
+    ///
+    /// The slot is only claimed by the first write, creating a writer at startup and never
+    /// writing to it leaves the stored messages as they are.
+    ///
+    /// # Safety
+    ///
+    /// No message returned by [`last_panics`](CrashRing::last_panics) can be used after the
+    /// writer is written to and only one writer can be used at a time.
+    pub unsafe fn writer(&self) -> CrashRingWriter<'_, N, S> {
+        CrashRingWriter {
+            ring: self,
+            slot: None,
+            claimed: false,
+        }
+    }
+
+    /// Claim the next slot, resetting the ring first if it contains garbage
+    unsafe fn claim(&self) -> Option<*mut Slot<S>> {
+        let ring = self.ring();
+        if !self.is_valid() {
+            for index in 0..N {
+                (*self.slot(index)).valid = 0;
+            }
+            (*ring).head = 0;
+            (*ring).magic = RING_MAGIC;
+        }
+
+        match N {
+            0 => None,
+            _ => {
+                let head = (*ring).head as usize % N;
+                (*ring).head = ((head + 1) % N) as u32;
+                let slot = self.slot(head);
+                (*slot).len = 0;
+                (*slot).valid = 1;
+                Some(slot)
+            }
+        }
+    }
+}
+
+unsafe fn slot_message<'a, const S: usize>(slot: *mut Slot<S>) -> Option<&'a str> {
+    if (*slot).valid != 1 || (*slot).len as usize > S {
+        return None;
+    }
+    let data = core::slice::from_raw_parts(
+        addr_of_mut!((*slot).data) as *const u8,
+        (*slot).len as usize,
+    );
+    Some(valid_prefix(data))
+}
+
+/// Writer for a single slot of a [`CrashRing`]
+pub struct CrashRingWriter<'a, const N: usize, const S: usize> {
+    ring: &'a CrashRing<N, S>,
+    slot: Option<*mut Slot<S>>,
+    /// Whether the first write claimed a slot, there is none if the ring is empty
+    claimed: bool,
+}
+
+impl<'a, const N: usize, const S: usize> Write for CrashRingWriter<'a, N, S> {
+    fn write_str(&mut self, s: &str) -> Result {
+        if !self.claimed {
+            self.claimed = true;
+            // safe because creating the writer required that only one writer is used at a time
+            self.slot = unsafe { self.ring.claim() };
+        }
+        if let Some(slot) = self.slot {
+            unsafe {
+                let position = ((*slot).len as usize).min(S);
+                let len = s.len().min(S - position);
+                let data = addr_of_mut!((*slot).data) as *mut u8;
+                core::ptr::copy_nonoverlapping(s.as_ptr(), data.add(position), len);
+                (*slot).len = (position + len) as u32;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the buffer is shared, so everything touching it is done in a single test
+    #[test]
+    fn persists_the_written_message() {
+        let mut writer = unsafe { PersistentWriter::new() };
+        assert_eq!(last_panic(), None);
+        assert!(!writer.truncated());
+
+        write!(writer, "panicked at {}", 42).unwrap();
+        assert_eq!(last_panic(), Some("panicked at 42"));
+
+        // a new writer only replaces the message once it's written to
+        let mut writer = unsafe { PersistentWriter::new() };
+        assert_eq!(last_panic(), Some("panicked at 42"));
+        for _ in 0..PERSISTENT_CAPACITY {
+            writer.write_str("é").unwrap();
+        }
+        assert!(writer.truncated());
+        let message = last_panic().unwrap();
+        assert_eq!(message.len(), PERSISTENT_CAPACITY);
+        assert!(message.chars().all(|c| c == 'é'));
+    }
+
+    /// A ring filled with garbage, like after a cold boot
+    fn cold_ring<const N: usize, const S: usize>() -> CrashRing<N, S> {
+        let ring = CrashRing::new();
+        unsafe { (ring.ring() as *mut u8).write_bytes(0xa5, core::mem::size_of::<Ring<N, S>>()) };
+        ring
+    }
+
+    #[test]
+    fn ignores_garbage_after_a_cold_boot() {
+        let ring = cold_ring::<3, 16>();
+        assert_eq!(ring.last_panics().count(), 0);
+
+        // the ring is only reset once a message is written
+        let _writer = unsafe { ring.writer() };
+        assert!(!ring.is_valid());
+
+        let mut writer = unsafe { ring.writer() };
+        writer.write_str("first").unwrap();
+        assert_eq!(ring.last_panics().collect::<Vec<_>>(), ["first"]);
+    }
+
+    #[test]
+    fn keeps_the_newest_messages() {
+        let ring = cold_ring::<3, 4>();
+        for message in ["one", "two", "three", "four"] {
+            let mut writer = unsafe { ring.writer() };
+            writer.write_str(message).unwrap();
+        }
+        assert_eq!(
+            ring.last_panics().collect::<Vec<_>>(),
+            ["two", "thre", "four"]
+        );
+
+        // an unused writer doesn't claim a slot
+        let _writer = unsafe { ring.writer() };
+        assert_eq!(ring.last_panics().count(), 3);
+        assert_eq!(ring.last_panics().next(), Some("two"));
+    }
+}
@@ -0,0 +1,154 @@
+//! Intrusive stack of registered panic handlers
+//!
+//! Every handler embeds a [`Node`], registering a handler pushes its node onto the stack and the
+//! panic handler walks the stack from the most recently registered handler down.
+
+use crate::action::Outcome;
+use crate::PanicInfo;
+use core::ptr::null_mut;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+pub(crate) type Trampoline = unsafe fn(handler: *mut (), info: &PanicInfo) -> Option<Outcome>;
+pub(crate) type Finisher = unsafe fn(handler: *mut ());
+pub(crate) type Poller = unsafe fn(handler: *mut ());
+
+pub(crate) struct Node {
+    next: *mut Node,
+    handler: *mut (),
+    /// The monomorphized trampoline for the handler containing this node
+    trampoline: Trampoline,
+    /// Finishes the output of the handler after a nested panic, without calling the hook
+    finisher: Finisher,
+    /// Waits for a non-blocking flush of the handler to complete, before the post-panic action
+    poller: Poller,
+}
+
+static HEAD: AtomicPtr<Node> = AtomicPtr::new(null_mut());
+
+impl Node {
+    pub(crate) const fn new(trampoline: Trampoline, finisher: Finisher, poller: Poller) -> Self {
+        Node {
+            next: null_mut(),
+            handler: null_mut(),
+            trampoline,
+            finisher,
+            poller,
+        }
+    }
+}
+
+/// Push a node onto the stack, a node that is already registered is moved to the top
+///
+/// # Safety
+///
+/// The node must stay valid and pinned until it is removed again.
+pub(crate) unsafe fn push(node: *mut Node, handler: *mut ()) {
+    locked(|| {
+        remove(node);
+        (*node).handler = handler;
+        (*node).next = HEAD.load(Ordering::Acquire);
+        HEAD.store(node, Ordering::Release);
+    })
+}
+
+/// Forget all registered nodes, the handlers they belong to can still be dropped normally
+#[cfg(any(test, feature = "simulate"))]
+pub(crate) fn clear() {
+    locked(|| HEAD.store(null_mut(), Ordering::Release))
+}
+
+/// Push a node onto the stack only if the stack is empty, returns whether the node was pushed
+///
+/// # Safety
+///
+/// The node must stay valid and pinned until it is removed again.
+pub(crate) unsafe fn try_push(node: *mut Node, handler: *mut ()) -> bool {
+    locked(|| {
+        if !HEAD.load(Ordering::Acquire).is_null() {
+            return false;
+        }
+        push(node, handler);
+        true
+    })
+}
+
+/// Remove a node from the stack, wherever it is, does nothing if the node isn't registered
+///
+/// # Safety
+///
+/// All registered nodes must be valid.
+pub(crate) unsafe fn remove(node: *mut Node) {
+    locked(|| {
+        let head = HEAD.load(Ordering::Acquire);
+        if head == node {
+            HEAD.store((*node).next, Ordering::Release);
+            return;
+        }
+
+        // handlers don't have to be deregistered in the order they were registered in
+        let mut current = head;
+        while !current.is_null() {
+            if (*current).next == node {
+                (*current).next = (*node).next;
+                return;
+            }
+            current = (*current).next;
+        }
+    })
+}
+
+/// Modify the stack inside a critical section, so cores or interrupts can't interleave their
+/// modifications
+#[cfg(feature = "critical-section")]
+pub(crate) fn locked<R>(f: impl FnOnce() -> R) -> R {
+    critical_section::with(|_| f())
+}
+
+#[cfg(not(feature = "critical-section"))]
+pub(crate) fn locked<R>(f: impl FnOnce() -> R) -> R {
+    f()
+}
+
+/// The handler pointer of the most recently registered node
+pub(crate) fn top() -> Option<*mut ()> {
+    let head = HEAD.load(Ordering::Acquire);
+    if head.is_null() {
+        None
+    } else {
+        Some(unsafe { (*head).handler })
+    }
+}
+
+/// Call `f` with the handler and trampoline of every registered node, most recent first
+pub(crate) fn for_each(mut f: impl FnMut(*mut (), Trampoline)) {
+    let mut current = HEAD.load(Ordering::Acquire);
+    while !current.is_null() {
+        // read the node before calling the trampoline, which borrows the handler containing it
+        let (next, handler, trampoline) =
+            unsafe { ((*current).next, (*current).handler, (*current).trampoline) };
+        f(handler, trampoline);
+        current = next;
+    }
+}
+
+/// Call `f` with the handler and finisher of every registered node, most recent first
+pub(crate) fn for_each_finisher(mut f: impl FnMut(*mut (), Finisher)) {
+    let mut current = HEAD.load(Ordering::Acquire);
+    while !current.is_null() {
+        let (next, handler, finisher) =
+            unsafe { ((*current).next, (*current).handler, (*current).finisher) };
+        f(handler, finisher);
+        current = next;
+    }
+}
+
+/// Call `f` with the handler and poller of every registered node, most recent first
+pub(crate) fn for_each_poller(mut f: impl FnMut(*mut (), Poller)) {
+    let mut current = HEAD.load(Ordering::Acquire);
+    while !current.is_null() {
+        let (next, handler, poller) =
+            unsafe { ((*current).next, (*current).handler, (*current).poller) };
+        f(handler, poller);
+        current = next;
+    }
+}
@@ -0,0 +1,137 @@
+use crate::PanicHandler;
+use crate::PanicInfo;
+use crate::Registered;
+use core::cell::UnsafeCell;
+use core::fmt::Write;
+use core::mem::MaybeUninit;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Storage for a panic handler in a `static`
+///
+/// ```ignore
+/// static HANDLER: StaticPanicHandler<Serial> = StaticPanicHandler::new();
+///
+/// fn main() {
+///     let serial = setup_serial();
+///     let serial = HANDLER.init_and_register(serial, format::location_hook);
+/// }
+/// ```
+///
+/// The handler can only be initialized once, after which it stays registered for the rest of
+/// the program.
+///
+/// A [`PanicHandler`] isn't `Sync`, so it can only be put in a `static` directly as a
+/// `static mut`. This wrapper is `Sync` as long as the writer and hook are `Send`: the handler is
+/// handed out to whoever initializes it, the writer and hook can end up being used from the
+/// context that panics, which might not be the one that initialized it.
+///
+/// On targets without atomic read-modify-write operations, like armv6-m, this is only available
+/// with the `critical-section` feature, which guarantees only one caller initializes the handler.
+pub struct StaticPanicHandler<W: Write, F: FnMut(&mut W, &PanicInfo) = fn(&mut W, &PanicInfo)> {
+    handler: UnsafeCell<MaybeUninit<PanicHandler<W, F>>>,
+    initialized: AtomicBool,
+}
+
+// the handler is only handed out once, to whoever initializes it
+unsafe impl<W: Write + Send, F: FnMut(&mut W, &PanicInfo) + Send> Sync
+    for StaticPanicHandler<W, F>
+{
+}
+
+impl<W: Write, F: FnMut(&mut W, &PanicInfo)> StaticPanicHandler<W, F> {
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        StaticPanicHandler {
+            handler: UnsafeCell::new(MaybeUninit::uninit()),
+            initialized: AtomicBool::new(false),
+        }
+    }
+
+    /// Initialize and register the handler, returning it for use in place of the writer
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handler was already initialized.
+    #[allow(clippy::mut_from_ref)]
+    pub fn init_and_register(
+        &'static self,
+        writer: W,
+        hook: F,
+    ) -> Pin<&'static mut PanicHandler<W, F, Registered>> {
+        if !self.claim() {
+            panic!("static panic handler is already initialized");
+        }
+
+        // safe because only the first call gets here
+        let handler = unsafe { &mut *self.handler.get() };
+        PanicHandler::register_static(handler.write(PanicHandler::new_with_hook(writer, hook)))
+    }
+
+    /// Mark the handler as initialized, returns whether this is the first call
+    #[cfg(target_has_atomic = "8")]
+    fn claim(&self) -> bool {
+        !self.initialized.swap(true, Ordering::AcqRel)
+    }
+
+    /// Mark the handler as initialized, returns whether this is the first call
+    #[cfg(all(not(target_has_atomic = "8"), feature = "critical-section"))]
+    fn claim(&self) -> bool {
+        // the check and set can't be a single atomic operation on this target, the critical
+        // section keeps other cores and interrupts from initializing in between
+        critical_section::with(|_| {
+            let first = !self.initialized.load(Ordering::Acquire);
+            self.initialized.store(true, Ordering::Release);
+            first
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::location_hook;
+    use crate::tests::globals;
+    use crate::{is_registered, simulate_panic_with};
+    use std::string::String;
+
+    #[test]
+    fn initializes_once_and_handles_panics() {
+        let _globals = globals();
+        static HANDLER: StaticPanicHandler<String> = StaticPanicHandler::new();
+        let mut handler = HANDLER.init_and_register(String::new(), location_hook);
+        assert!(is_registered());
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(
+            PanicHandler::detach(handler.as_mut()),
+            "PANIC <unknown> boom"
+        );
+        assert!(!HANDLER.claim());
+    }
+
+    /// Writes to a global, so the output can be read from a thread other than the one that
+    /// initialized the handler
+    struct GlobalWriter;
+
+    static WRITTEN: std::sync::Mutex<String> = std::sync::Mutex::new(String::new());
+
+    impl Write for GlobalWriter {
+        fn write_str(&mut self, s: &str) -> core::fmt::Result {
+            WRITTEN.lock().unwrap().push_str(s);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn handles_panics_from_another_thread_than_the_one_that_initialized_it() {
+        let _globals = globals();
+        static HANDLER: StaticPanicHandler<GlobalWriter> = StaticPanicHandler::new();
+        std::thread::spawn(|| {
+            HANDLER.init_and_register(GlobalWriter, location_hook);
+        })
+        .join()
+        .unwrap();
+        simulate_panic_with(format_args!("elsewhere"), None);
+        assert_eq!(*WRITTEN.lock().unwrap(), "PANIC <unknown> elsewhere");
+    }
+}
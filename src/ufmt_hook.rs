@@ -0,0 +1,73 @@
+use crate::PanicInfo;
+use core::fmt::Write;
+use ufmt::uwrite;
+
+/// Hook that writes the panic as `panicked at <file>:<line>:<col>: <message>` using `ufmt`
+///
+/// `ufmt` produces a lot less code than `core::fmt`, as long as nothing else in the firmware
+/// uses `core::fmt` this can save a significant amount of flash. The flip side is that formatted
+/// panic messages like `panic!("value: {}", value)` need `core::fmt`, so for those only
+/// `<formatted message>` is written, plain messages like `panic!("message")` are written as is.
+pub fn ufmt_hook<W: Write>(out: &mut W, info: &PanicInfo) {
+    let mut out = UfmtWriter(out);
+    let _ = match info.location() {
+        Some(location) => uwrite!(
+            out,
+            "panicked at {}:{}:{}: ",
+            location.file(),
+            location.line(),
+            location.column()
+        ),
+        None => uwrite!(out, "panicked: "),
+    };
+    let _ = match crate::message_str(info) {
+        Some("") => uwrite!(out, "panic (no info)"),
+        Some(message) => uwrite!(out, "{}", message),
+        None => uwrite!(out, "<formatted message>"),
+    };
+}
+
+/// Adapter for using a `core::fmt::Write` as a `ufmt::uWrite`, for writing custom hooks with
+/// `ufmt`
+pub struct UfmtWriter<'a, W: Write>(pub &'a mut W);
+
+impl<'a, W: Write> ufmt::uWrite for UfmtWriter<'a, W> {
+    type Error = core::fmt::Error;
+
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.0.write_str(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulate_panic_with;
+    use crate::tests::globals;
+    use crate::PanicHandler;
+
+    #[test]
+    fn writes_the_location_and_plain_message() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_hook(String::new(), ufmt_hook));
+        let registration = handler.register();
+        let location = core::panic::Location::caller();
+        simulate_panic_with(format_args!("boom"), Some(location));
+        assert_eq!(*registration, format!("panicked at {}: boom", location));
+    }
+
+    #[test]
+    fn formatted_messages_get_a_placeholder() {
+        let value = std::hint::black_box(42);
+        let mut out = String::new();
+        ufmt_hook(
+            &mut out,
+            &PanicInfo::new(format_args!("value: {}", value), None),
+        );
+        assert_eq!(out, "panicked: <formatted message>");
+
+        out.clear();
+        ufmt_hook(&mut out, &PanicInfo::new(format_args!(""), None));
+        assert_eq!(out, "panicked: panic (no info)");
+    }
+}
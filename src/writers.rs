@@ -0,0 +1,83 @@
+//! Writers and writer adapters for use with a [`PanicHandler`](crate::PanicHandler)
+
+mod base64;
+mod buffered;
+mod capping;
+mod checksummed;
+mod chunked;
+mod cobs;
+mod color;
+mod context;
+mod counting;
+mod crlf;
+#[cfg(feature = "cortex-m")]
+mod disable_irq;
+#[cfg(feature = "embedded-io")]
+mod eio;
+mod escaping;
+mod fallback;
+mod feeding;
+mod flash;
+mod hex;
+#[cfg(all(feature = "cortex-m", has_itm))]
+mod itm;
+mod length_prefix;
+mod line_flush;
+mod line_prefix;
+#[cfg(feature = "log")]
+mod logger;
+mod multi;
+mod raw_uart;
+mod ring;
+#[cfg(feature = "semihosting")]
+mod semihosting;
+#[cfg(feature = "embedded-hal-nb")]
+mod serial;
+#[cfg(feature = "critical-section")]
+mod shared;
+mod sink;
+mod slice;
+mod stats;
+mod tee;
+mod timestamp;
+
+pub use base64::Base64Writer;
+pub use buffered::BufferedWriter;
+pub use capping::CappingWriter;
+pub use checksummed::{verify_checksum, ChecksummedWriter};
+pub use chunked::ChunkedWriter;
+pub use cobs::CobsWriter;
+pub use color::ColorWriter;
+pub use context::ContextWriter;
+pub use counting::CountingWriter;
+pub use crlf::CrlfWriter;
+#[cfg(feature = "cortex-m")]
+pub use disable_irq::DisableIrqWriter;
+#[cfg(feature = "embedded-io")]
+pub use eio::EioWriter;
+pub use escaping::EscapingWriter;
+pub use fallback::FallbackWriter;
+pub use feeding::FeedingWriter;
+pub use flash::{DefaultFlashWriter, FlashWriter};
+pub use hex::HexWriter;
+#[cfg(all(feature = "cortex-m", has_itm))]
+pub use itm::ItmWriter;
+pub use length_prefix::{DefaultLengthPrefixWriter, LengthPrefixWriter};
+pub use line_flush::LineFlushWriter;
+pub use line_prefix::LinePrefixWriter;
+#[cfg(feature = "log")]
+pub use logger::LogWriter;
+pub use multi::MultiWriter;
+pub use raw_uart::RawUartWriter;
+pub use ring::RingWriter;
+#[cfg(feature = "semihosting")]
+pub use semihosting::SemihostingWriter;
+#[cfg(feature = "embedded-hal-nb")]
+pub use serial::SerialWriter;
+#[cfg(feature = "critical-section")]
+pub use shared::SharedWriter;
+pub use sink::Sink;
+pub use slice::SliceWriter;
+pub use stats::{Stats, StatsWriter};
+pub use tee::TeeWriter;
+pub use timestamp::TimestampWriter;
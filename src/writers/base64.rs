@@ -0,0 +1,127 @@
+use crate::PanicWriter;
+use core::fmt::{Result, Write};
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Writer that base64 encodes everything written to it, for text channels that mangle control
+/// bytes
+///
+/// Bytes are encoded in groups of three, a group split across writes is completed by the next
+/// write. The last partial group and its padding are written by
+/// [`finish`](Base64Writer::finish).
+pub struct Base64Writer<W: Write> {
+    inner: W,
+    pending: [u8; 3],
+    pending_len: usize,
+}
+
+impl<W: Write> Base64Writer<W> {
+    pub fn new(inner: W) -> Self {
+        Base64Writer {
+            inner,
+            pending: [0; 3],
+            pending_len: 0,
+        }
+    }
+
+    /// Write the last partial group with padding, the next write starts a new encoding
+    pub fn finish(&mut self) -> Result {
+        if self.pending_len == 0 {
+            return Ok(());
+        }
+        let mut encoded = encode(&self.pending, self.pending_len);
+        encoded[self.pending_len + 1..].fill(b'=');
+        self.pending_len = 0;
+        // the encoded bytes are all ascii
+        self.inner
+            .write_str(unsafe { core::str::from_utf8_unchecked(&encoded) })
+    }
+
+    pub fn inner(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// Encode the first `len` bytes of a group, the digits past `len + 1` are garbage
+fn encode(group: &[u8; 3], len: usize) -> [u8; 4] {
+    let mut bytes = [0; 3];
+    bytes[..len].copy_from_slice(&group[..len]);
+    let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+    [
+        ALPHABET[(bits >> 18) as usize & 0x3f],
+        ALPHABET[(bits >> 12) as usize & 0x3f],
+        ALPHABET[(bits >> 6) as usize & 0x3f],
+        ALPHABET[bits as usize & 0x3f],
+    ]
+}
+
+impl<W: Write> Write for Base64Writer<W> {
+    fn write_str(&mut self, s: &str) -> Result {
+        // encode in chunks to keep the number of calls to the inner writer down
+        let mut encoded = [0; 32];
+        let mut len = 0;
+        for byte in s.bytes() {
+            self.pending[self.pending_len] = byte;
+            self.pending_len += 1;
+            if self.pending_len < 3 {
+                continue;
+            }
+            self.pending_len = 0;
+            encoded[len..len + 4].copy_from_slice(&encode(&self.pending, 3));
+            len += 4;
+            if len == encoded.len() {
+                // the encoded bytes are all ascii
+                self.inner
+                    .write_str(unsafe { core::str::from_utf8_unchecked(&encoded) })?;
+                len = 0;
+            }
+        }
+        // the rest of the input is kept until the group is complete
+        self.inner
+            .write_str(unsafe { core::str::from_utf8_unchecked(&encoded[..len]) })
+    }
+}
+
+impl<W: Write> PanicWriter for Base64Writer<W> {
+    fn finish(&mut self) {
+        let _ = Base64Writer::finish(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_all(parts: &[&str]) -> String {
+        let mut writer = Base64Writer::new(String::new());
+        for part in parts {
+            writer.write_str(part).unwrap();
+        }
+        writer.finish().unwrap();
+        writer.into_inner()
+    }
+
+    #[test]
+    fn pads_the_last_group() {
+        assert_eq!(encode_all(&[""]), "");
+        assert_eq!(encode_all(&["f"]), "Zg==");
+        assert_eq!(encode_all(&["fo"]), "Zm8=");
+        assert_eq!(encode_all(&["foo"]), "Zm9v");
+        assert_eq!(encode_all(&["foobar"]), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn groups_continue_across_writes() {
+        assert_eq!(encode_all(&["f", "oob", "a", "r"]), "Zm9vYmFy");
+        let long = "panicked at src/main.rs:42:5: boom";
+        assert_eq!(
+            encode_all(&[long]),
+            "cGFuaWNrZWQgYXQgc3JjL21haW4ucnM6NDI6NTogYm9vbQ=="
+        );
+        assert_eq!(encode_all(&[&long[..20], &long[20..]]), encode_all(&[long]));
+    }
+}
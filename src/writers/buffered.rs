@@ -0,0 +1,128 @@
+use crate::{Flush, PanicWriter};
+use core::fmt::{Result, Write};
+use core::mem::ManuallyDrop;
+
+/// Writer that collects output in a buffer of `N` bytes before forwarding it to the inner writer
+///
+/// This reduces the number of calls to slow writers. The buffer is forwarded when it's full or
+/// when the writer is flushed, use
+/// [`PanicHandler::new_with_flush`](crate::PanicHandler::new_with_flush) with `Flush::flush` to
+/// forward the remainder after the panic message. Output that is still buffered when the writer
+/// is dropped is flushed as well.
+pub struct BufferedWriter<W: Write, const N: usize> {
+    inner: W,
+    buffer: [u8; N],
+    len: usize,
+}
+
+impl<W: Write, const N: usize> BufferedWriter<W, N> {
+    pub fn new(inner: W) -> Self {
+        BufferedWriter {
+            inner,
+            buffer: [0; N],
+            len: 0,
+        }
+    }
+
+    /// Forward the buffered output to the inner writer
+    pub fn flush_buffer(&mut self) -> Result {
+        if self.len == 0 {
+            return Ok(());
+        }
+        // only whole strings or chunks split at char boundaries are buffered
+        let buffered = unsafe { core::str::from_utf8_unchecked(&self.buffer[..self.len]) };
+        self.len = 0;
+        self.inner.write_str(buffered)
+    }
+
+    pub fn inner(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Get back the inner writer, discarding any buffered output that wasn't flushed
+    pub fn into_inner(self) -> W {
+        // skip the flush on drop, the other fields don't need dropping
+        let this = ManuallyDrop::new(self);
+        unsafe { core::ptr::read(&this.inner) }
+    }
+}
+
+impl<W: Write, const N: usize> Write for BufferedWriter<W, N> {
+    fn write_str(&mut self, mut s: &str) -> Result {
+        while !s.is_empty() {
+            if s.len() > N - self.len {
+                self.flush_buffer()?;
+            }
+
+            // split strings bigger than the buffer at a char boundary
+            let mut len = s.len().min(N);
+            while !s.is_char_boundary(len) {
+                len -= 1;
+            }
+            if len == 0 {
+                // a single char that doesn't fit in the buffer at all
+                len = s.chars().next().map_or(0, char::len_utf8);
+                self.inner.write_str(&s[..len])?;
+            } else {
+                self.buffer[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+                self.len += len;
+            }
+            s = &s[len..];
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write, const N: usize> Flush for BufferedWriter<W, N> {
+    fn flush(&mut self) {
+        let _ = self.flush_buffer();
+    }
+}
+
+impl<W: Write, const N: usize> PanicWriter for BufferedWriter<W, N> {
+    fn flush(&mut self) {
+        let _ = self.flush_buffer();
+    }
+}
+
+impl<W: Write, const N: usize> Drop for BufferedWriter<W, N> {
+    fn drop(&mut self) {
+        let _ = self.flush_buffer();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forwards_when_full_or_flushed() {
+        let mut writer = BufferedWriter::<_, 4>::new(String::new());
+        writer.write_str("abc").unwrap();
+        assert_eq!(writer.inner(), "");
+        writer.write_str("de").unwrap();
+        assert_eq!(writer.inner(), "abc");
+        writer.flush_buffer().unwrap();
+        assert_eq!(writer.into_inner(), "abcde");
+    }
+
+    #[test]
+    fn splits_long_strings_at_char_boundaries() {
+        let mut writer = BufferedWriter::<_, 3>::new(String::new());
+        writer.write_str("aäbcü€").unwrap();
+        assert_eq!(writer.inner(), "aäbcü");
+        // a char that doesn't fit in the buffer at all is written directly
+        let mut small = BufferedWriter::<_, 1>::new(String::new());
+        small.write_str("a€").unwrap();
+        assert_eq!(small.inner(), "a€");
+    }
+
+    #[test]
+    fn dropping_flushes_the_buffer() {
+        let mut out = String::new();
+        let mut writer = BufferedWriter::<_, 8>::new(&mut out);
+        writer.write_str("abc").unwrap();
+        drop(writer);
+        assert_eq!(out, "abc");
+    }
+}
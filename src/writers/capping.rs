@@ -0,0 +1,112 @@
+use core::fmt::{Result, Write};
+
+/// Writer that stops forwarding output to the inner writer after a maximum number of bytes
+///
+/// Unlike [`SliceWriter`](crate::writers::SliceWriter) this works with any writer, e.g. to
+/// bound how long a long panic message ties up a slow serial port. An optional marker like
+/// `"..."` is written once the limit is hit, in addition to the limited output.
+pub struct CappingWriter<W: Write> {
+    inner: W,
+    max_bytes: usize,
+    written: usize,
+    truncated: bool,
+    marker: &'static str,
+}
+
+impl<W: Write> CappingWriter<W> {
+    pub fn new(inner: W, max_bytes: usize) -> Self {
+        CappingWriter::with_marker(inner, max_bytes, "")
+    }
+
+    /// Create a writer that writes `marker` when output is dropped
+    pub fn with_marker(inner: W, max_bytes: usize, marker: &'static str) -> Self {
+        CappingWriter {
+            inner,
+            max_bytes,
+            written: 0,
+            truncated: false,
+            marker,
+        }
+    }
+
+    /// Change the maximum number of bytes, including the bytes already written
+    pub fn set_max_bytes(&mut self, max_bytes: usize) {
+        self.max_bytes = max_bytes;
+    }
+
+    /// Whether any output was dropped
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    pub fn inner(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for CappingWriter<W> {
+    fn write_str(&mut self, s: &str) -> Result {
+        if self.truncated {
+            return Ok(());
+        }
+        let remaining = self.max_bytes.saturating_sub(self.written);
+        if s.len() <= remaining {
+            self.inner.write_str(s)?;
+            self.written += s.len();
+            return Ok(());
+        }
+
+        // don't cut a character in half
+        let mut len = remaining;
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.truncated = true;
+        self.written += len;
+        self.inner.write_str(&s[..len])?;
+        self.inner.write_str(self.marker)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::globals;
+    use crate::{simulate_panic_with, PanicHandlerBuilder, PanicInfo};
+
+    #[test]
+    fn stops_at_the_limit() {
+        let mut writer = CappingWriter::with_marker(String::new(), 6, "...");
+        writer.write_str("boom").unwrap();
+        assert!(!writer.truncated());
+        writer.write_str(" at main.rs").unwrap();
+        writer.write_str("!").unwrap();
+        assert!(writer.truncated());
+        assert_eq!(writer.into_inner(), "boom a...");
+    }
+
+    #[test]
+    fn doesnt_split_a_char() {
+        let mut writer = CappingWriter::new(String::new(), 4);
+        writer.write_str("aaaä").unwrap();
+        assert_eq!(writer.into_inner(), "aaa");
+    }
+
+    #[test]
+    fn builder_limit_overrides_the_writer() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandlerBuilder::new()
+            .hook(|out: &mut CappingWriter<String>, info: &PanicInfo| {
+                let _ = write!(out, "{}", info.message());
+            })
+            .max_bytes(4)
+            .build(CappingWriter::new(String::new(), 100)));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom at main.rs"), None);
+        assert_eq!(registration.detach().into_inner(), "boom");
+    }
+}
@@ -0,0 +1,117 @@
+use crate::PanicWriter;
+use core::fmt::{Result, Write};
+
+/// Length of the footer written by [`ChecksummedWriter::finish`]
+const FOOTER_LEN: usize = 16;
+
+/// Writer that ends the output with a footer containing its length and CRC32
+///
+/// The footer is written by [`finish`](ChecksummedWriter::finish) as 16 hex digits, 8 for the
+/// length followed by 8 for the checksum, so the output stays valid text. Messages recovered
+/// after a reset, e.g. from a [`PersistentWriter`](crate::PersistentWriter), can be checked
+/// with [`verify_checksum`].
+pub struct ChecksummedWriter<W: Write> {
+    inner: W,
+    len: u32,
+    crc: u32,
+}
+
+impl<W: Write> ChecksummedWriter<W> {
+    pub fn new(inner: W) -> Self {
+        ChecksummedWriter {
+            inner,
+            len: 0,
+            crc: !0,
+        }
+    }
+
+    /// Write the footer for everything written since the last footer
+    pub fn finish(&mut self) -> Result {
+        let (len, crc) = (self.len, !self.crc);
+        self.len = 0;
+        self.crc = !0;
+        write!(self.inner, "{:08x}{:08x}", len, crc)
+    }
+
+    pub fn inner(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for ChecksummedWriter<W> {
+    fn write_str(&mut self, s: &str) -> Result {
+        self.inner.write_str(s)?;
+        self.len = self.len.wrapping_add(s.len() as u32);
+        self.crc = crc32(self.crc, s.as_bytes());
+        Ok(())
+    }
+}
+
+impl<W: Write> PanicWriter for ChecksummedWriter<W> {
+    fn finish(&mut self) {
+        let _ = ChecksummedWriter::finish(self);
+    }
+}
+
+/// Check the footer written by a [`ChecksummedWriter`] and return the message without it
+///
+/// Returns `None` if the footer is missing or doesn't match the message.
+pub fn verify_checksum(bytes: &[u8]) -> Option<&str> {
+    let split = bytes.len().checked_sub(FOOTER_LEN)?;
+    let (message, footer) = bytes.split_at(split);
+    let footer = core::str::from_utf8(footer).ok()?;
+    let len = u32::from_str_radix(footer.get(..8)?, 16).ok()?;
+    let crc = u32::from_str_radix(footer.get(8..)?, 16).ok()?;
+
+    if len as usize != message.len() || crc != !crc32(!0, message) {
+        return None;
+    }
+    core::str::from_utf8(message).ok()
+}
+
+/// Bitwise CRC32 (IEEE), trading speed for not needing a lookup table
+fn crc32(mut crc: u32, bytes: &[u8]) -> u32 {
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_matches_the_ieee_check_value() {
+        assert_eq!(!crc32(!0, b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn footer_round_trips() {
+        let mut writer = ChecksummedWriter::new(String::new());
+        write!(writer, "boom {}", 42).unwrap();
+        writer.finish().unwrap();
+        let out = writer.into_inner();
+        assert_eq!(out.len(), "boom 42".len() + FOOTER_LEN);
+        assert_eq!(verify_checksum(out.as_bytes()), Some("boom 42"));
+    }
+
+    #[test]
+    fn rejects_a_corrupted_message() {
+        let mut writer = ChecksummedWriter::new(String::new());
+        writer.write_str("boom").unwrap();
+        writer.finish().unwrap();
+        let mut out = writer.into_inner().into_bytes();
+        out[0] = b'z';
+        assert_eq!(verify_checksum(&out), None);
+        assert_eq!(verify_checksum(b"boom"), None);
+    }
+}
@@ -0,0 +1,102 @@
+use core::fmt::{Result, Write};
+
+/// Writer that forwards output in chunks of at most `chunk_size` bytes and calls a closure after
+/// every chunk, e.g. to service a supervisor timer on a cooperative system
+///
+/// Unlike a [`FeedingWriter`](super::FeedingWriter) the closure runs after a fixed amount of
+/// output instead of a number of writes, so a single long write doesn't delay it. Chunks end on
+/// char boundaries, a char longer than `chunk_size` is forwarded whole.
+pub struct ChunkedWriter<W: Write, Y: FnMut()> {
+    inner: W,
+    chunk_size: usize,
+    yield_now: Y,
+}
+
+impl<W: Write, Y: FnMut()> ChunkedWriter<W, Y> {
+    pub fn new(inner: W, chunk_size: usize, yield_now: Y) -> Self {
+        ChunkedWriter {
+            inner,
+            chunk_size: chunk_size.max(1),
+            yield_now,
+        }
+    }
+
+    pub fn inner(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write, Y: FnMut()> Write for ChunkedWriter<W, Y> {
+    fn write_str(&mut self, s: &str) -> Result {
+        let mut rest = s;
+        while !rest.is_empty() {
+            let mut end = self.chunk_size.min(rest.len());
+            while !rest.is_char_boundary(end) {
+                end -= 1;
+            }
+            if end == 0 {
+                end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+            }
+            let (chunk, next) = rest.split_at(end);
+            self.inner.write_str(chunk)?;
+            (self.yield_now)();
+            rest = next;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::globals;
+    use crate::{simulate_panic_with, PanicHandler, PanicInfo};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static YIELDS: AtomicUsize = AtomicUsize::new(0);
+
+    fn count_yield() {
+        YIELDS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn yields_after_every_chunk_of_the_panic() {
+        let _globals = globals();
+        YIELDS.store(0, Ordering::Relaxed);
+        let handler = core::pin::pin!(PanicHandler::new_with_hook(
+            ChunkedWriter::new(String::new(), 4, count_yield as fn()),
+            |out: &mut ChunkedWriter<String, fn()>, info: &PanicInfo| {
+                let _ = out.write_str(info.message().as_str().unwrap());
+            }
+        ));
+        let mut registration = handler.register();
+        simulate_panic_with(format_args!("boom boom"), None);
+        assert_eq!(registration.inner(), "boom boom");
+        assert_eq!(YIELDS.load(Ordering::Relaxed), 3);
+    }
+
+    /// Keeps every write as a separate chunk
+    #[derive(Default)]
+    struct Chunks(Vec<String>);
+
+    impl Write for Chunks {
+        fn write_str(&mut self, s: &str) -> Result {
+            self.0.push(s.into());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn chunks_end_on_char_boundaries() {
+        let mut yields = 0;
+        let mut writer = ChunkedWriter::new(Chunks::default(), 2, || yields += 1);
+        writer.write_str("a€bc").unwrap();
+        // "€" is three bytes, more than a chunk, so it's forwarded whole
+        assert_eq!(writer.into_inner().0, ["a", "€", "bc"]);
+        assert_eq!(yields, 3);
+    }
+}
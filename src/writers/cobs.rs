@@ -0,0 +1,122 @@
+use crate::PanicWriter;
+use core::fmt::{Result, Write};
+
+/// Maximum number of data bytes in a COBS block
+const BLOCK: usize = 254;
+
+/// Writer that COBS encodes its output, for self delimiting frames on a serial link
+///
+/// Encoded output isn't valid utf-8, so it's passed to a `sink` taking bytes instead of a
+/// `Write`, e.g. `CobsWriter::new(|bytes: &[u8]| uart.bwrite_all(bytes).ok())`. The output is
+/// encoded as it's written, only the current block of up to 254 bytes is buffered.
+/// [`finish`](CobsWriter::finish) writes the last block and the zero delimiter, use
+/// [`PanicHandler::new_panic_writer`](crate::PanicHandler::new_panic_writer) to end the frame
+/// after the panic message.
+pub struct CobsWriter<S: FnMut(&[u8])> {
+    sink: S,
+    block: [u8; BLOCK],
+    len: usize,
+}
+
+impl<S: FnMut(&[u8])> CobsWriter<S> {
+    pub fn new(sink: S) -> Self {
+        CobsWriter {
+            sink,
+            block: [0; BLOCK],
+            len: 0,
+        }
+    }
+
+    /// Write the last block and the frame delimiter, the next output starts a new frame
+    pub fn finish(&mut self) {
+        self.write_block();
+        (self.sink)(&[0]);
+    }
+
+    pub fn inner(&mut self) -> &mut S {
+        &mut self.sink
+    }
+
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+
+    fn write_block(&mut self) {
+        (self.sink)(&[self.len as u8 + 1]);
+        (self.sink)(&self.block[..self.len]);
+        self.len = 0;
+    }
+}
+
+impl<S: FnMut(&[u8])> Write for CobsWriter<S> {
+    fn write_str(&mut self, s: &str) -> Result {
+        for byte in s.bytes() {
+            if byte == 0 {
+                self.write_block();
+                continue;
+            }
+            self.block[self.len] = byte;
+            self.len += 1;
+            // a full block has no implicit zero after it
+            if self.len == BLOCK {
+                self.write_block();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<S: FnMut(&[u8])> PanicWriter for CobsWriter<S> {
+    fn finish(&mut self) {
+        CobsWriter::finish(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(s: &str) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut writer = CobsWriter::new(|bytes: &[u8]| out.extend_from_slice(bytes));
+        writer.write_str(s).unwrap();
+        writer.finish();
+        out
+    }
+
+    /// Decode a single frame, including its delimiter
+    fn decode(frame: &[u8]) -> Vec<u8> {
+        let (delimiter, mut encoded) = frame.split_last().unwrap();
+        assert_eq!(*delimiter, 0);
+        let mut out = Vec::new();
+        while let Some((&code, rest)) = encoded.split_first() {
+            assert_ne!(code, 0, "zero in the frame");
+            let (block, rest) = rest.split_at(code as usize - 1);
+            out.extend_from_slice(block);
+            encoded = rest;
+            if code as usize != BLOCK + 1 && !encoded.is_empty() {
+                out.push(0);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn encodes_zeros() {
+        assert_eq!(encode("a\0bc"), [2, b'a', 3, b'b', b'c', 0]);
+        assert_eq!(encode(""), [1, 0]);
+    }
+
+    #[test]
+    fn round_trips() {
+        let long = "x".repeat(300) + "\0" + &"y".repeat(254);
+        for message in ["boom", "\0", "a\0\0b\0", &long] {
+            let frame = encode(message);
+            assert_eq!(
+                frame.iter().position(|&byte| byte == 0),
+                Some(frame.len() - 1)
+            );
+            assert_eq!(decode(&frame), message.as_bytes());
+        }
+    }
+}
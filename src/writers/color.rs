@@ -0,0 +1,132 @@
+use crate::{Flush, PanicWriter};
+use core::fmt::{Result, Write};
+
+const BOLD_RED: &str = "\x1b[1;31m";
+const RESET: &str = "\x1b[0m";
+
+/// Writer that colors the output using ANSI escape sequences
+///
+/// The color is set before the first byte is written and reset by [`finish`](ColorWriter::finish),
+/// use [`PanicHandler::new_panic_writer`](crate::PanicHandler::new_panic_writer) to reset the
+/// color after the panic message.
+pub struct ColorWriter<W: Write> {
+    inner: W,
+    color: Option<&'static str>,
+    started: bool,
+}
+
+impl<W: Write> ColorWriter<W> {
+    /// Create a writer that colors the output bold red
+    pub fn new(inner: W) -> Self {
+        ColorWriter::with_color(inner, BOLD_RED)
+    }
+
+    /// Create a writer with a custom SGR escape sequence, e.g. `"\x1b[33m"`
+    pub fn with_color(inner: W, color: &'static str) -> Self {
+        ColorWriter {
+            inner,
+            color: Some(color),
+            started: false,
+        }
+    }
+
+    /// Create a writer that doesn't emit any escape sequences, for terminals without color
+    /// support
+    pub fn plain(inner: W) -> Self {
+        ColorWriter {
+            inner,
+            color: None,
+            started: false,
+        }
+    }
+
+    /// Reset the color if any output was written since the last reset
+    pub fn finish(&mut self) -> Result {
+        if self.started {
+            self.started = false;
+            self.inner.write_str(RESET)?;
+        }
+        Ok(())
+    }
+
+    pub fn inner(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for ColorWriter<W> {
+    fn write_str(&mut self, s: &str) -> Result {
+        if let (Some(color), false) = (self.color, self.started) {
+            self.started = true;
+            self.inner.write_str(color)?;
+        }
+        self.inner.write_str(s)
+    }
+}
+
+impl<W: Write> Flush for ColorWriter<W> {
+    fn flush(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+impl<W: Write> PanicWriter for ColorWriter<W> {
+    fn finish(&mut self) {
+        let _ = ColorWriter::finish(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::globals;
+    use crate::{simulate_panic_with, PanicHandler, PanicInfo};
+
+    fn message_hook(out: &mut ColorWriter<String>, info: &PanicInfo) {
+        let _ = write!(out, "{}", info.message());
+    }
+
+    #[test]
+    fn colors_the_panic_message() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_flush(
+            ColorWriter::new(String::new()),
+            message_hook,
+            Flush::flush
+        ));
+        let mut registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(registration.inner(), "\x1b[1;31mboom\x1b[0m");
+    }
+
+    #[test]
+    fn resets_the_color_as_a_panic_writer() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_panic_writer(
+            ColorWriter::new(String::new()),
+            message_hook
+        ));
+        let mut registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(registration.inner(), "\x1b[1;31mboom\x1b[0m");
+    }
+
+    #[test]
+    fn plain_writes_no_escape_sequences() {
+        let mut writer = ColorWriter::plain(String::new());
+        writer.write_str("boom").unwrap();
+        writer.finish().unwrap();
+        assert_eq!(writer.into_inner(), "boom");
+    }
+
+    #[test]
+    fn nothing_written_leaves_no_escape_sequences() {
+        let mut writer = ColorWriter::new(String::new());
+        writer.finish().unwrap();
+        assert_eq!(writer.into_inner(), "");
+    }
+}
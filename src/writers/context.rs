@@ -0,0 +1,40 @@
+use core::fmt::{Result, Write};
+
+/// Writer that carries a context next to the writer it forwards to
+///
+/// Used by [`PanicHandler::new_with_ctx`](crate::PanicHandler::new_with_ctx) to hand the hook
+/// state that isn't the writer, like a peripheral, and to give it back with the writer when the
+/// handler is detached.
+pub struct ContextWriter<W: Write, C> {
+    inner: W,
+    context: C,
+}
+
+impl<W: Write, C> ContextWriter<W, C> {
+    pub fn new(inner: W, context: C) -> Self {
+        ContextWriter { inner, context }
+    }
+
+    pub fn inner(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn context(&mut self) -> &mut C {
+        &mut self.context
+    }
+
+    /// Borrow the writer and the context at the same time
+    pub fn parts(&mut self) -> (&mut W, &mut C) {
+        (&mut self.inner, &mut self.context)
+    }
+
+    pub fn into_inner(self) -> (W, C) {
+        (self.inner, self.context)
+    }
+}
+
+impl<W: Write, C> Write for ContextWriter<W, C> {
+    fn write_str(&mut self, s: &str) -> Result {
+        self.inner.write_str(s)
+    }
+}
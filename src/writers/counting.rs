@@ -0,0 +1,63 @@
+use core::fmt::{Result, Write};
+
+/// Writer that counts the number of bytes written to the inner writer
+pub struct CountingWriter<W: Write> {
+    inner: W,
+    count: usize,
+}
+
+impl<W: Write> CountingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        CountingWriter { inner, count: 0 }
+    }
+
+    /// The number of bytes successfully written so far
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn inner(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write_str(&mut self, s: &str) -> Result {
+        self.inner.write_str(s)?;
+        self.count += s.len();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_the_written_bytes() {
+        let mut writer = CountingWriter::new(String::new());
+        write!(writer, "boom {}", 42).unwrap();
+        writer.write_str(" ä").unwrap();
+        assert_eq!(writer.count(), 10);
+        assert_eq!(writer.into_inner(), "boom 42 ä");
+    }
+
+    #[test]
+    fn doesnt_count_failed_writes() {
+        struct Failing;
+
+        impl Write for Failing {
+            fn write_str(&mut self, _s: &str) -> Result {
+                Err(core::fmt::Error)
+            }
+        }
+
+        let mut writer = CountingWriter::new(Failing);
+        assert!(writer.write_str("boom").is_err());
+        assert_eq!(writer.count(), 0);
+    }
+}
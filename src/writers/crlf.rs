@@ -0,0 +1,76 @@
+use core::fmt::{Result, Write};
+
+/// Writer that translates `\n` into `\r\n` for serial terminals
+///
+/// Existing `\r\n` sequences and lone `\r` are forwarded unchanged. Unlike the
+/// [`line_ending`](crate::PanicHandlerBuilder::line_ending) option this translates every newline,
+/// so multi-line panic messages don't render as a staircase.
+pub struct CrlfWriter<W: Write> {
+    inner: W,
+    /// Whether the last byte written was `\r`, a `\r\n` can be split over two writes
+    after_cr: bool,
+}
+
+impl<W: Write> CrlfWriter<W> {
+    pub fn new(inner: W) -> Self {
+        CrlfWriter {
+            inner,
+            after_cr: false,
+        }
+    }
+
+    pub fn inner(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for CrlfWriter<W> {
+    fn write_str(&mut self, s: &str) -> Result {
+        let mut start = 0;
+        for (i, byte) in s.bytes().enumerate() {
+            let after_cr = if i == 0 {
+                self.after_cr
+            } else {
+                s.as_bytes()[i - 1] == b'\r'
+            };
+            if byte == b'\n' && !after_cr {
+                self.inner.write_str(&s[start..i])?;
+                self.inner.write_str("\r")?;
+                start = i;
+            }
+        }
+        self.inner.write_str(&s[start..])?;
+        if let Some(last) = s.bytes().last() {
+            self.after_cr = last == b'\r';
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::globals;
+    use crate::{simulate_panic_with, PanicHandler};
+
+    #[test]
+    fn translates_the_newlines_of_the_panic() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new(CrlfWriter::new(String::new())));
+        let mut registration = handler.register();
+        simulate_panic_with(format_args!("boom\nsecond line"), None);
+        assert_eq!(registration.inner(), "panicked at :\r\nboom\r\nsecond line");
+    }
+
+    #[test]
+    fn keeps_existing_crlf_split_over_writes() {
+        let mut writer = CrlfWriter::new(String::new());
+        writer.write_str("a\r").unwrap();
+        writer.write_str("\nb\rc\n").unwrap();
+        assert_eq!(writer.into_inner(), "a\r\nb\rc\r\n");
+    }
+}
@@ -0,0 +1,135 @@
+use crate::PanicWriter;
+use core::fmt::{Result, Write};
+#[cfg(test)]
+use tests::interrupt;
+
+/// Writer that disables interrupts while the panic message is written
+///
+/// Interrupts are disabled before the first write and restored to their previous state by
+/// [`finish`](DisableIrqWriter::finish), so nesting inside an outer critical section is fine. Use
+/// [`PanicHandler::new_panic_writer`](crate::PanicHandler::new_panic_writer) to restore the
+/// interrupts after the panic message.
+pub struct DisableIrqWriter<W: Write> {
+    inner: W,
+    /// Whether interrupts were enabled before the first write, if they are currently disabled by
+    /// us
+    saved: Option<bool>,
+}
+
+impl<W: Write> DisableIrqWriter<W> {
+    pub fn new(inner: W) -> Self {
+        DisableIrqWriter { inner, saved: None }
+    }
+
+    /// Restore the interrupt state from before the first write
+    pub fn finish(&mut self) {
+        if let Some(true) = self.saved.take() {
+            // safe because interrupts were enabled before we disabled them
+            unsafe { interrupt::enable() };
+        }
+    }
+
+    pub fn inner(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(mut self) -> W {
+        self.finish();
+        self.inner
+    }
+}
+
+impl<W: Write> Write for DisableIrqWriter<W> {
+    fn write_str(&mut self, s: &str) -> Result {
+        if self.saved.is_none() {
+            self.saved = Some(interrupt::active());
+            interrupt::disable();
+        }
+        self.inner.write_str(s)
+    }
+}
+
+#[cfg(not(test))]
+mod interrupt {
+    pub use cortex_m::interrupt::{disable, enable};
+    use cortex_m::register::primask;
+
+    pub fn active() -> bool {
+        primask::read().is_active()
+    }
+}
+
+impl<W: Write> PanicWriter for DisableIrqWriter<W> {
+    fn finish(&mut self) {
+        DisableIrqWriter::finish(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::globals;
+    use crate::{simulate_panic_with, PanicHandler, PanicInfo};
+    use core::cell::Cell;
+
+    /// Stands in for the interrupt mask of the core, which the host doesn't have
+    pub(super) mod interrupt {
+        use super::*;
+
+        std::thread_local! {
+            pub(super) static ENABLED: Cell<bool> = const { Cell::new(true) };
+        }
+
+        pub fn active() -> bool {
+            ENABLED.get()
+        }
+
+        pub fn disable() {
+            ENABLED.set(false);
+        }
+
+        pub unsafe fn enable() {
+            ENABLED.set(true);
+        }
+    }
+
+    /// Records whether interrupts were enabled during the last write
+    #[derive(Default)]
+    struct Probe {
+        enabled_while_writing: Option<bool>,
+    }
+
+    impl Write for Probe {
+        fn write_str(&mut self, _: &str) -> Result {
+            self.enabled_while_writing = Some(interrupt::active());
+            Ok(())
+        }
+    }
+
+    fn hook(out: &mut DisableIrqWriter<Probe>, info: &PanicInfo) {
+        let _ = write!(out, "{}", info.message());
+    }
+
+    #[test]
+    fn interrupts_are_disabled_while_writing() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_panic_writer(
+            DisableIrqWriter::new(Probe::default()),
+            hook
+        ));
+        let mut registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(registration.inner().enabled_while_writing, Some(false));
+        assert!(interrupt::active());
+    }
+
+    #[test]
+    fn interrupts_that_were_disabled_stay_disabled() {
+        interrupt::ENABLED.set(false);
+        let mut writer = DisableIrqWriter::new(Probe::default());
+        writer.write_str("boom").unwrap();
+        writer.finish();
+        assert!(!interrupt::active());
+        interrupt::ENABLED.set(true);
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{Flush, PanicWriter};
+use core::fmt::{Error, Result, Write};
+
+/// Writer for anything implementing `embedded_io::Write`
+///
+/// Writes block until the whole string is written, partial writes are retried with the
+/// remainder. Any io error is reported as a `core::fmt::Error`.
+pub struct EioWriter<T: embedded_io::Write> {
+    inner: T,
+}
+
+impl<T: embedded_io::Write> EioWriter<T> {
+    pub fn new(inner: T) -> Self {
+        EioWriter { inner }
+    }
+
+    pub fn inner(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: embedded_io::Write> Write for EioWriter<T> {
+    fn write_str(&mut self, s: &str) -> Result {
+        self.inner.write_all(s.as_bytes()).map_err(|_| Error)
+    }
+}
+
+impl<T: embedded_io::Write> Flush for EioWriter<T> {
+    fn flush(&mut self) {
+        let _ = self.inner.flush();
+    }
+}
+
+impl<T: embedded_io::Write> PanicWriter for EioWriter<T> {
+    fn flush(&mut self) {
+        let _ = self.inner.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::globals;
+    use crate::{simulate_panic_with, PanicHandler};
+    use core::convert::Infallible;
+
+    /// Writer that accepts at most 3 bytes per write
+    #[derive(Default)]
+    struct MockIo {
+        written: Vec<u8>,
+        flushed: usize,
+    }
+
+    impl embedded_io::ErrorType for MockIo {
+        type Error = Infallible;
+    }
+
+    impl embedded_io::Write for MockIo {
+        fn write(&mut self, buf: &[u8]) -> core::result::Result<usize, Infallible> {
+            let len = buf.len().min(3);
+            self.written.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> core::result::Result<(), Infallible> {
+            self.flushed += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn retries_partial_writes_and_flushes() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_panic_writer(
+            EioWriter::new(MockIo::default()),
+            crate::format::location_hook
+        ));
+        let mut registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(registration.inner().written, b"PANIC <unknown> boom");
+        assert_eq!(registration.inner().flushed, 1);
+    }
+}
@@ -0,0 +1,75 @@
+use core::fmt::{Result, Write};
+
+/// Writer that replaces control characters with `\xNN` escapes, so a panic message can't inject
+/// terminal escape sequences or confuse line based parsers on the host
+///
+/// All bytes below `0x20` are escaped, except for `\n` unless created with
+/// [`escape_all`](EscapingWriter::escape_all). A message containing `"\x1b[31m"` is written as
+/// the literal text `\x1b[31m`.
+pub struct EscapingWriter<W: Write> {
+    inner: W,
+    keep_newlines: bool,
+}
+
+impl<W: Write> EscapingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        EscapingWriter {
+            inner,
+            keep_newlines: true,
+        }
+    }
+
+    /// Create a writer that escapes newlines too, keeping the whole message on one line
+    pub fn escape_all(inner: W) -> Self {
+        EscapingWriter {
+            inner,
+            keep_newlines: false,
+        }
+    }
+
+    pub fn inner(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for EscapingWriter<W> {
+    fn write_str(&mut self, s: &str) -> Result {
+        let mut start = 0;
+        for (i, byte) in s.bytes().enumerate() {
+            if byte >= 0x20 || (byte == b'\n' && self.keep_newlines) {
+                continue;
+            }
+            self.inner.write_str(&s[start..i])?;
+            write!(self.inner, "\\x{:02x}", byte)?;
+            start = i + 1;
+        }
+        self.inner.write_str(&s[start..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::globals;
+    use crate::{simulate_panic_with, PanicHandler};
+
+    #[test]
+    fn escapes_control_characters_in_the_panic() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new(EscapingWriter::new(String::new())));
+        let mut registration = handler.register();
+        simulate_panic_with(format_args!("\x1b[31mred\tboom"), None);
+        assert_eq!(registration.inner(), "panicked at :\n\\x1b[31mred\\x09boom");
+    }
+
+    #[test]
+    fn escape_all_escapes_newlines() {
+        let mut writer = EscapingWriter::escape_all(String::new());
+        writer.write_str("one\ntwo").unwrap();
+        assert_eq!(writer.into_inner(), "one\\x0atwo");
+    }
+}
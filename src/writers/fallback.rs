@@ -0,0 +1,113 @@
+use core::fmt::{Result, Write};
+
+/// Writer that forwards to a primary writer and switches to a secondary writer once the primary
+/// fails
+///
+/// The write that failed is repeated on the secondary writer, everything after it only goes to
+/// the secondary writer. Unlike [`TeeWriter`](crate::writers::TeeWriter) only one of the writers
+/// receives any given output.
+pub struct FallbackWriter<A: Write, B: Write> {
+    a: A,
+    b: B,
+    failed: bool,
+}
+
+impl<A: Write, B: Write> FallbackWriter<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        FallbackWriter {
+            a,
+            b,
+            failed: false,
+        }
+    }
+
+    /// Whether the primary writer failed and output is going to the secondary writer
+    pub fn used_fallback(&self) -> bool {
+        self.failed
+    }
+
+    pub fn inner_a(&mut self) -> &mut A {
+        &mut self.a
+    }
+
+    pub fn inner_b(&mut self) -> &mut B {
+        &mut self.b
+    }
+
+    pub fn into_inner(self) -> (A, B) {
+        (self.a, self.b)
+    }
+}
+
+impl<A: Write, B: Write> Write for FallbackWriter<A, B> {
+    fn write_str(&mut self, s: &str) -> Result {
+        if !self.failed {
+            if self.a.write_str(s).is_ok() {
+                return Ok(());
+            }
+            self.failed = true;
+        }
+        self.b.write_str(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::globals;
+    use crate::{simulate_panic_with, PanicHandler, PanicInfo};
+
+    /// Writer that fails once it has accepted `remaining` writes
+    struct FailsAfter {
+        out: String,
+        remaining: usize,
+    }
+
+    impl Write for FailsAfter {
+        fn write_str(&mut self, s: &str) -> Result {
+            if self.remaining == 0 {
+                return Err(core::fmt::Error);
+            }
+            self.remaining -= 1;
+            self.out.push_str(s);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn switches_to_the_secondary_writer_on_failure() {
+        let primary = FailsAfter {
+            out: String::new(),
+            remaining: 1,
+        };
+        let mut writer = FallbackWriter::new(primary, String::new());
+        writer.write_str("one ").unwrap();
+        assert!(!writer.used_fallback());
+        writer.write_str("two ").unwrap();
+        writer.write_str("three").unwrap();
+        assert!(writer.used_fallback());
+        let (a, b) = writer.into_inner();
+        assert_eq!(a.out, "one ");
+        assert_eq!(b, "two three");
+    }
+
+    #[test]
+    fn panic_message_reaches_the_fallback() {
+        let _globals = globals();
+        let primary = FailsAfter {
+            out: String::new(),
+            remaining: 0,
+        };
+        let handler = core::pin::pin!(PanicHandler::new_with_hook(
+            FallbackWriter::new(primary, String::new()),
+            |out: &mut FallbackWriter<FailsAfter, String>, info: &PanicInfo| {
+                let _ = write!(out, "{}", info);
+            }
+        ));
+        let mut registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert!(registration.used_fallback());
+        assert_eq!(registration.inner_a().out, "");
+        assert!(registration.inner_b().contains("boom"));
+    }
+}
@@ -0,0 +1,85 @@
+use core::fmt::{Result, Write};
+
+/// Writer that calls a closure every few writes, e.g. to feed a watchdog during long panic
+/// messages over a slow transport
+pub struct FeedingWriter<W: Write, Fp: FnMut()> {
+    inner: W,
+    feed: Fp,
+    interval: usize,
+    writes: usize,
+}
+
+impl<W: Write, Fp: FnMut()> FeedingWriter<W, Fp> {
+    /// Create a writer that calls `feed` after every write
+    pub fn new(inner: W, feed: Fp) -> Self {
+        FeedingWriter::with_interval(inner, 1, feed)
+    }
+
+    /// Create a writer that calls `feed` after every `interval` writes
+    pub fn with_interval(inner: W, interval: usize, feed: Fp) -> Self {
+        FeedingWriter {
+            inner,
+            feed,
+            interval: interval.max(1),
+            writes: 0,
+        }
+    }
+
+    pub fn inner(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write, Fp: FnMut()> Write for FeedingWriter<W, Fp> {
+    fn write_str(&mut self, s: &str) -> Result {
+        let result = self.inner.write_str(s);
+        self.writes += 1;
+        if self.writes >= self.interval {
+            self.writes = 0;
+            (self.feed)();
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::globals;
+    use crate::{simulate_panic_with, PanicHandler, PanicInfo};
+    use core::cell::Cell;
+
+    #[test]
+    fn feeds_while_the_message_is_written() {
+        let _globals = globals();
+        let feeds = Cell::new(0);
+        let handler = core::pin::pin!(PanicHandler::new_with_watchdog(
+            String::new(),
+            |out: &mut FeedingWriter<String, _>, info: &PanicInfo| {
+                for word in info.message().as_str().unwrap().split(' ') {
+                    let _ = out.write_str(word);
+                }
+            },
+            || feeds.set(feeds.get() + 1)
+        ));
+        let mut registration = handler.register();
+        simulate_panic_with(format_args!("a slow message"), None);
+        assert_eq!(registration.inner(), "aslowmessage");
+        assert_eq!(feeds.get(), 3);
+    }
+
+    #[test]
+    fn feeds_every_interval_writes() {
+        let mut feeds = 0;
+        let mut writer = FeedingWriter::with_interval(String::new(), 2, || feeds += 1);
+        for _ in 0..5 {
+            writer.write_str("a").unwrap();
+        }
+        assert_eq!(writer.into_inner(), "aaaaa");
+        assert_eq!(feeds, 2);
+    }
+}
@@ -0,0 +1,176 @@
+use crate::PanicWriter;
+use core::fmt::{Result, Write};
+
+/// Writer that stages the panic message in RAM and commits it to a flash page on
+/// [`finish`](FlashWriter::finish)
+///
+/// The message is limited to `N` bytes, so pick a size that fits the longest expected message and
+/// the RAM budget, [`DefaultFlashWriter`] uses 256 bytes. Longer output is dropped and reported by
+/// [`truncated`](FlashWriter::truncated). On finish `erase` is called with the page, after which
+/// `program` is called once with the offset and the message, padded with `0xff` to a multiple of
+/// the write alignment. Since `0xff` never occurs in utf-8 the end of the message can be found
+/// when reading it back. Use
+/// [`PanicHandler::new_panic_writer`](crate::PanicHandler::new_panic_writer) to commit the panic
+/// message once it's written.
+///
+/// ```ignore
+/// let flash = DefaultFlashWriter::new(
+///     CRASH_PAGE,
+///     CRASH_PAGE_ADDRESS,
+///     |page| flash.erase_page(page),
+///     |offset, bytes| flash.program(offset, bytes),
+/// )
+/// .with_alignment(8);
+/// ```
+pub struct FlashWriter<E: FnMut(usize), P: FnMut(usize, &[u8]), const N: usize> {
+    erase: E,
+    program: P,
+    page: usize,
+    offset: usize,
+    alignment: usize,
+    buffer: [u8; N],
+    len: usize,
+    truncated: bool,
+    committed: bool,
+}
+
+/// A [`FlashWriter`] with a 256 byte buffer
+pub type DefaultFlashWriter<E, P> = FlashWriter<E, P, 256>;
+
+impl<E: FnMut(usize), P: FnMut(usize, &[u8]), const N: usize> FlashWriter<E, P, N> {
+    pub fn new(page: usize, offset: usize, erase: E, program: P) -> Self {
+        FlashWriter {
+            erase,
+            program,
+            page,
+            offset,
+            alignment: 1,
+            buffer: [0; N],
+            len: 0,
+            truncated: false,
+            committed: false,
+        }
+    }
+
+    /// Pad the message to a multiple of `alignment` bytes, for flash that can only be written
+    /// in units of multiple bytes
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` isn't a multiple of `alignment`.
+    // `is_multiple_of` needs a newer toolchain than the crate supports
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn with_alignment(mut self, alignment: usize) -> Self {
+        assert!(
+            alignment > 0 && N % alignment == 0,
+            "the buffer size must be a multiple of the alignment"
+        );
+        self.alignment = alignment;
+        self
+    }
+
+    /// Whether any output was dropped because the buffer was full
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Erase the page and write the staged message, only the first call commits the message
+    // `div_ceil` needs a newer toolchain than the crate supports
+    #[allow(clippy::manual_div_ceil)]
+    pub fn finish(&mut self) {
+        if self.committed {
+            return;
+        }
+        self.committed = true;
+
+        let padded = (self.len + self.alignment - 1) / self.alignment * self.alignment;
+        self.buffer[self.len..padded].fill(0xff);
+        (self.erase)(self.page);
+        (self.program)(self.offset, &self.buffer[..padded]);
+    }
+}
+
+impl<E: FnMut(usize), P: FnMut(usize, &[u8]), const N: usize> Write for FlashWriter<E, P, N> {
+    fn write_str(&mut self, s: &str) -> Result {
+        // output after the commit can't be written anymore
+        if self.committed {
+            return Ok(());
+        }
+        let len = s.len().min(N - self.len);
+        self.buffer[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.len += len;
+        self.truncated |= len < s.len();
+        Ok(())
+    }
+}
+
+impl<E: FnMut(usize), P: FnMut(usize, &[u8]), const N: usize> PanicWriter for FlashWriter<E, P, N> {
+    fn finish(&mut self) {
+        FlashWriter::finish(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::globals;
+    use crate::{simulate_panic_with, PanicHandler};
+    use std::cell::RefCell;
+
+    #[test]
+    fn commits_the_padded_message_after_the_panic() {
+        let _globals = globals();
+        let flash = RefCell::new(Vec::new());
+        let handler = core::pin::pin!(PanicHandler::new_panic_writer(
+            FlashWriter::<_, _, 32>::new(
+                3,
+                0x800,
+                |page| flash.borrow_mut().push(format!("erase {}", page)),
+                |offset, bytes: &[u8]| flash
+                    .borrow_mut()
+                    .push(format!("program {:#x} {:?}", offset, bytes)),
+            )
+            .with_alignment(4),
+            crate::format::location_hook
+        ));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert!(!registration.truncated());
+        assert_eq!(
+            *flash.borrow(),
+            [
+                String::from("erase 3"),
+                format!("program 0x800 {:?}", b"PANIC <unknown> boom"),
+            ]
+        );
+    }
+
+    #[test]
+    fn pads_to_the_alignment_and_truncates() {
+        let mut programmed = Vec::new();
+        let mut writer = FlashWriter::<_, _, 8>::new(
+            0,
+            0,
+            |_| {},
+            |_, bytes: &[u8]| programmed = bytes.to_vec(),
+        )
+        .with_alignment(4);
+        writer.write_str("abcde").unwrap();
+        writer.finish();
+        assert_eq!(programmed, b"abcde\xff\xff\xff");
+
+        let mut writer = FlashWriter::<_, _, 4>::new(0, 0, |_| {}, |_, _: &[u8]| {});
+        writer.write_str("abcde").unwrap();
+        assert!(writer.truncated());
+    }
+
+    #[test]
+    fn truncates_past_the_capacity() {
+        let mut small = FlashWriter::<_, _, 4>::new(0, 0, |_| {}, |_, _: &[u8]| {});
+        let mut large = DefaultFlashWriter::new(0, 0, |_| {}, |_, _: &[u8]| {});
+        small.write_str("hello").unwrap();
+        large.write_str("hello").unwrap();
+        assert!(small.truncated());
+        assert!(!large.truncated());
+    }
+}
@@ -0,0 +1,76 @@
+use core::fmt::{Result, Write};
+
+/// Writer that hex encodes everything written to it, for transports that can't carry arbitrary
+/// text
+///
+/// Every byte is written as two hex digits, lowercase unless created with
+/// [`uppercase`](HexWriter::uppercase).
+pub struct HexWriter<W: Write> {
+    inner: W,
+    digits: &'static [u8; 16],
+}
+
+impl<W: Write> HexWriter<W> {
+    pub fn new(inner: W) -> Self {
+        HexWriter {
+            inner,
+            digits: b"0123456789abcdef",
+        }
+    }
+
+    /// Create a writer that uses uppercase hex digits
+    pub fn uppercase(inner: W) -> Self {
+        HexWriter {
+            inner,
+            digits: b"0123456789ABCDEF",
+        }
+    }
+
+    pub fn inner(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for HexWriter<W> {
+    fn write_str(&mut self, s: &str) -> Result {
+        // encode in chunks to keep the number of calls to the inner writer down
+        let mut encoded = [0; 32];
+        for chunk in s.as_bytes().chunks(encoded.len() / 2) {
+            for (i, byte) in chunk.iter().enumerate() {
+                encoded[i * 2] = self.digits[(byte >> 4) as usize];
+                encoded[i * 2 + 1] = self.digits[(byte & 0xf) as usize];
+            }
+            // the encoded bytes are all ascii hex digits
+            let encoded = unsafe { core::str::from_utf8_unchecked(&encoded[..chunk.len() * 2]) };
+            self.inner.write_str(encoded)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_every_byte() {
+        let mut writer = HexWriter::new(String::new());
+        writer.write_str("ok\n\u{ff}").unwrap();
+        assert_eq!(writer.into_inner(), "6f6b0ac3bf");
+
+        let mut writer = HexWriter::uppercase(String::new());
+        writer.write_str("\u{ff}").unwrap();
+        assert_eq!(writer.into_inner(), "C3BF");
+    }
+
+    #[test]
+    fn encodes_strings_longer_than_a_chunk() {
+        let mut writer = HexWriter::new(String::new());
+        writer.write_str(&"a".repeat(40)).unwrap();
+        assert_eq!(writer.into_inner(), "61".repeat(40));
+    }
+}
@@ -0,0 +1,36 @@
+use core::fmt::{Result, Write};
+use cortex_m::peripheral::itm::Stim;
+
+/// Writer for an ITM stimulus port, for routing the panic message over SWO trace
+///
+/// ```ignore
+/// let itm = unsafe { &mut *cortex_m::peripheral::ITM::PTR };
+/// let handler = pin!(PanicHandler::new(ItmWriter::new(&mut itm.stim[0])));
+/// ```
+///
+/// The message is packed into word sized writes where possible, waiting for room in the FIFO
+/// before every write.
+pub struct ItmWriter<'a> {
+    port: &'a mut Stim,
+}
+
+impl<'a> ItmWriter<'a> {
+    pub fn new(port: &'a mut Stim) -> Self {
+        ItmWriter { port }
+    }
+
+    pub fn inner(&mut self) -> &mut Stim {
+        self.port
+    }
+
+    pub fn into_inner(self) -> &'a mut Stim {
+        self.port
+    }
+}
+
+impl<'a> Write for ItmWriter<'a> {
+    fn write_str(&mut self, s: &str) -> Result {
+        cortex_m::itm::write_str(self.port, s);
+        Ok(())
+    }
+}
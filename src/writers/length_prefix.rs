@@ -0,0 +1,129 @@
+use crate::PanicWriter;
+use core::fmt::{Result, Write};
+
+/// Writer that stages the output and writes it with a length header in front, for framed
+/// protocols that need the length before the payload
+///
+/// The output is kept in a buffer of `N` bytes until [`finish`](LengthPrefixWriter::finish),
+/// which writes the length as 8 hex digits followed by the buffered output. Output that doesn't
+/// fit in the buffer is dropped, the header always matches the bytes that follow it and
+/// [`truncated`](LengthPrefixWriter::truncated) tells if anything was dropped. Use
+/// [`DefaultLengthPrefixWriter`] for a 256 byte buffer.
+pub struct LengthPrefixWriter<W: Write, const N: usize> {
+    inner: W,
+    buffer: [u8; N],
+    len: usize,
+    truncated: bool,
+}
+
+/// A [`LengthPrefixWriter`] with a 256 byte buffer
+pub type DefaultLengthPrefixWriter<W> = LengthPrefixWriter<W, 256>;
+
+impl<W: Write, const N: usize> LengthPrefixWriter<W, N> {
+    pub fn new(inner: W) -> Self {
+        LengthPrefixWriter {
+            inner,
+            buffer: [0; N],
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    /// Whether any output was dropped because the buffer was full
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Write the header and the buffered output, the next write starts a new frame
+    pub fn finish(&mut self) -> Result {
+        let len = self.len;
+        self.len = 0;
+        self.truncated = false;
+        // only complete chars are buffered
+        let payload = unsafe { core::str::from_utf8_unchecked(&self.buffer[..len]) };
+        write!(self.inner, "{:08x}", len)?;
+        self.inner.write_str(payload)
+    }
+
+    pub fn inner(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write, const N: usize> Write for LengthPrefixWriter<W, N> {
+    fn write_str(&mut self, s: &str) -> Result {
+        // keep the payload contiguous, nothing after a gap is kept
+        if self.truncated {
+            return Ok(());
+        }
+        let mut len = s.len().min(N - self.len);
+        // don't split a char, the payload has to stay valid utf-8
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.buffer[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.len += len;
+        self.truncated |= len < s.len();
+        Ok(())
+    }
+}
+
+impl<W: Write, const N: usize> PanicWriter for LengthPrefixWriter<W, N> {
+    fn finish(&mut self) {
+        let _ = LengthPrefixWriter::finish(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::globals;
+    use crate::{simulate_panic_with, PanicHandler, PanicInfo};
+
+    fn message_hook(out: &mut LengthPrefixWriter<String, 16>, info: &PanicInfo) {
+        let _ = write!(out, "{}", info.message());
+    }
+
+    #[test]
+    fn writes_the_length_before_the_panic() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_panic_writer(
+            LengthPrefixWriter::<_, 16>::new(String::new()),
+            message_hook
+        ));
+        let mut registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(registration.inner(), "00000004boom");
+    }
+
+    #[test]
+    fn the_length_matches_the_truncated_payload() {
+        let mut writer = LengthPrefixWriter::<_, 4>::new(String::new());
+        writer.write_str("ab€").unwrap();
+        writer.write_str("c").unwrap();
+        assert!(writer.truncated());
+        writer.finish().unwrap();
+        assert!(!writer.truncated());
+        assert_eq!(writer.into_inner(), "00000002ab");
+    }
+
+    #[test]
+    fn truncates_past_the_capacity() {
+        let mut small = LengthPrefixWriter::<_, 4>::new(String::new());
+        let mut large = DefaultLengthPrefixWriter::new(String::new());
+        for writer in [&mut small as &mut dyn Write, &mut large] {
+            writer.write_str("hello").unwrap();
+            writer.write_str(" world").unwrap();
+        }
+        assert!(small.truncated());
+        assert!(!large.truncated());
+        small.finish().unwrap();
+        large.finish().unwrap();
+        assert_eq!(small.into_inner(), "00000004hell");
+        assert_eq!(large.into_inner(), "0000000bhello world");
+    }
+}
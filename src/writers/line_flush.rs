@@ -0,0 +1,137 @@
+use crate::{Flush, PanicWriter};
+use core::fmt::{Result, Write};
+use core::mem::ManuallyDrop;
+
+/// Writer that flushes the inner writer after every newline
+///
+/// Pairs with buffered writers like [`BufferedWriter`](crate::writers::BufferedWriter), so
+/// every complete line has been forwarded even if a fault interrupts the panic handler before its
+/// final flush. The trailing partial line is flushed by [`PanicWriter::finish`] and when the
+/// writer is dropped, use [`PanicHandler::new_panic_writer`](crate::PanicHandler::new_panic_writer)
+/// to flush it after the panic message.
+pub struct LineFlushWriter<W: Write + Flush> {
+    inner: W,
+    /// Whether anything was written since the last flush
+    pending: bool,
+}
+
+impl<W: Write + Flush> LineFlushWriter<W> {
+    pub fn new(inner: W) -> Self {
+        LineFlushWriter {
+            inner,
+            pending: false,
+        }
+    }
+
+    pub fn inner(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        // skip the flush on drop
+        let this = ManuallyDrop::new(self);
+        unsafe { core::ptr::read(&this.inner) }
+    }
+
+    fn flush_pending(&mut self) {
+        if self.pending {
+            self.pending = false;
+            self.inner.flush();
+        }
+    }
+}
+
+impl<W: Write + Flush> Write for LineFlushWriter<W> {
+    fn write_str(&mut self, s: &str) -> Result {
+        let mut rest = s;
+        while let Some(end) = rest.find('\n') {
+            let (line, next) = rest.split_at(end + 1);
+            self.inner.write_str(line)?;
+            self.inner.flush();
+            self.pending = false;
+            rest = next;
+        }
+        if rest.is_empty() {
+            Ok(())
+        } else {
+            self.pending = true;
+            self.inner.write_str(rest)
+        }
+    }
+}
+
+impl<W: Write + Flush> Flush for LineFlushWriter<W> {
+    fn flush(&mut self) {
+        self.pending = false;
+        self.inner.flush();
+    }
+}
+
+impl<W: Write + Flush> PanicWriter for LineFlushWriter<W> {
+    fn flush(&mut self) {
+        self.flush_pending();
+    }
+
+    fn finish(&mut self) {
+        self.flush_pending();
+    }
+}
+
+impl<W: Write + Flush> Drop for LineFlushWriter<W> {
+    fn drop(&mut self) {
+        self.flush_pending();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::globals;
+    use crate::{simulate_panic_with, PanicHandler, PanicInfo};
+
+    /// Records how much output had been written at every flush
+    #[derive(Default)]
+    struct Recorder {
+        out: String,
+        flushes: Vec<usize>,
+    }
+
+    impl Write for Recorder {
+        fn write_str(&mut self, s: &str) -> Result {
+            self.out.push_str(s);
+            Ok(())
+        }
+    }
+
+    impl Flush for Recorder {
+        fn flush(&mut self) {
+            self.flushes.push(self.out.len());
+        }
+    }
+
+    #[test]
+    fn flushes_after_every_line() {
+        let mut writer = LineFlushWriter::new(Recorder::default());
+        writer.write_str("one\ntwo\nthr").unwrap();
+        writer.write_str("ee\n").unwrap();
+        assert_eq!(writer.inner().flushes, [4, 8, 14]);
+        assert_eq!(writer.into_inner().out, "one\ntwo\nthree\n");
+    }
+
+    #[test]
+    fn partial_line_is_flushed_after_the_panic() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_panic_writer(
+            LineFlushWriter::new(Recorder::default()),
+            |out: &mut LineFlushWriter<Recorder>, info: &PanicInfo| {
+                let _ = write!(out, "panicked:\n{}", info.message());
+            }
+        ));
+        let mut registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        let recorder = registration.inner();
+        assert_eq!(recorder.out, "panicked:\nboom");
+        assert_eq!(recorder.flushes.first(), Some(&10));
+        assert_eq!(recorder.flushes.last(), Some(&14));
+    }
+}
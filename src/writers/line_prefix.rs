@@ -0,0 +1,71 @@
+use core::fmt::{Result, Write};
+
+/// Writer that starts every line with a prefix, e.g. `"PANIC| "`
+///
+/// Unlike the [`prefix`](crate::PanicHandlerBuilder::prefix) option, which is only written once,
+/// this makes every line of a multi-line message easy to find in aggregated logs. The prefix is
+/// written when the first character of a line is, so output ending in a newline doesn't leave a
+/// dangling prefix.
+pub struct LinePrefixWriter<W: Write> {
+    inner: W,
+    prefix: &'static str,
+    line_start: bool,
+}
+
+impl<W: Write> LinePrefixWriter<W> {
+    pub fn new(inner: W, prefix: &'static str) -> Self {
+        LinePrefixWriter {
+            inner,
+            prefix,
+            line_start: true,
+        }
+    }
+
+    pub fn inner(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for LinePrefixWriter<W> {
+    fn write_str(&mut self, s: &str) -> Result {
+        let mut rest = s;
+        while !rest.is_empty() {
+            if self.line_start {
+                self.inner.write_str(self.prefix)?;
+                self.line_start = false;
+            }
+            let end = rest.find('\n').map_or(rest.len(), |end| end + 1);
+            let (line, next) = rest.split_at(end);
+            self.inner.write_str(line)?;
+            self.line_start = line.ends_with('\n');
+            rest = next;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::globals;
+    use crate::{simulate_panic_with, PanicHandler};
+
+    #[test]
+    fn prefixes_every_line_of_the_panic() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new(LinePrefixWriter::new(
+            String::new(),
+            "PANIC| "
+        )));
+        let mut registration = handler.register();
+        simulate_panic_with(format_args!("one\ntwo\n"), None);
+        assert_eq!(
+            registration.inner(),
+            "PANIC| panicked at :\nPANIC| one\nPANIC| two\n"
+        );
+    }
+}
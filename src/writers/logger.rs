@@ -0,0 +1,139 @@
+use crate::{Flush, PanicWriter};
+use core::fmt::{Result, Write};
+
+/// Writer that logs every line of the panic message with `log::error!`, so panics go through
+/// the same logger as the rest of the application
+///
+/// Lines are buffered until they end, lines longer than `N` bytes are logged in multiple parts.
+/// The last line is logged when the writer is flushed or finished, use
+/// [`PanicHandler::new_panic_writer`](crate::PanicHandler::new_panic_writer) to have the handler
+/// do that. The logger has to keep working in the panic handler, so it shouldn't rely on
+/// interrupts to make progress.
+pub struct LogWriter<const N: usize = 128> {
+    buffer: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> LogWriter<N> {
+    pub const fn new() -> Self {
+        LogWriter {
+            buffer: [0; N],
+            len: 0,
+        }
+    }
+
+    /// Log the buffered part of the current line, if any
+    pub fn flush(&mut self) {
+        if self.len == 0 {
+            return;
+        }
+        // only complete chars are buffered
+        let line = unsafe { core::str::from_utf8_unchecked(&self.buffer[..self.len]) };
+        ::log::error!("{}", line);
+        self.len = 0;
+    }
+
+    fn push(&mut self, mut s: &str) {
+        while !s.is_empty() {
+            let space = N - self.len;
+            if s.len() <= space {
+                self.buffer[self.len..self.len + s.len()].copy_from_slice(s.as_bytes());
+                self.len += s.len();
+                return;
+            }
+
+            let mut split = space;
+            while !s.is_char_boundary(split) {
+                split -= 1;
+            }
+            // a buffer too small for a single char can't log anything
+            if split == 0 && self.len == 0 {
+                return;
+            }
+            let (part, rest) = s.split_at(split);
+            self.buffer[self.len..self.len + part.len()].copy_from_slice(part.as_bytes());
+            self.len += part.len();
+            self.flush();
+            s = rest;
+        }
+    }
+}
+
+impl<const N: usize> Default for LogWriter<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Write for LogWriter<N> {
+    fn write_str(&mut self, s: &str) -> Result {
+        for line in s.split_inclusive('\n') {
+            match line.strip_suffix('\n') {
+                Some(line) => {
+                    self.push(line);
+                    self.flush();
+                }
+                None => self.push(line),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<const N: usize> Flush for LogWriter<N> {
+    fn flush(&mut self) {
+        LogWriter::flush(self);
+    }
+}
+
+impl<const N: usize> PanicWriter for LogWriter<N> {
+    fn flush(&mut self) {
+        LogWriter::flush(self);
+    }
+
+    fn finish(&mut self) {
+        LogWriter::flush(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::globals;
+    use crate::{simulate_panic_with, PanicHandler};
+    use std::sync::Mutex;
+
+    static LINES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    /// Logger that collects the error messages in `LINES`
+    struct Collector;
+
+    impl log::Log for Collector {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() == log::Level::Error
+        }
+
+        fn log(&self, record: &log::Record) {
+            LINES.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn logs_every_line_of_the_panic() {
+        let _globals = globals();
+        let _ = log::set_logger(&Collector);
+        log::set_max_level(log::LevelFilter::Error);
+        let handler = core::pin::pin!(PanicHandler::new_panic_writer(
+            LogWriter::<8>::new(),
+            crate::format::location_hook
+        ));
+        let _registration = handler.register();
+        simulate_panic_with(format_args!("boom\nsecond line"), None);
+        assert_eq!(
+            *LINES.lock().unwrap(),
+            ["PANIC <u", "nknown> ", "boom", "second l", "ine"]
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use core::fmt::{Result, Write};
+
+/// Writer that forwards everything written to an array of writers of the same type
+///
+/// A failure in one writer doesn't stop the remaining writers from being written to.
+pub struct MultiWriter<W: Write, const N: usize> {
+    writers: [W; N],
+}
+
+impl<W: Write, const N: usize> MultiWriter<W, N> {
+    pub fn new(writers: [W; N]) -> Self {
+        MultiWriter { writers }
+    }
+
+    pub fn inner(&self) -> &[W; N] {
+        &self.writers
+    }
+
+    pub fn into_inner(self) -> [W; N] {
+        self.writers
+    }
+}
+
+impl<W: Write, const N: usize> Write for MultiWriter<W, N> {
+    fn write_str(&mut self, s: &str) -> Result {
+        let mut result = Ok(());
+        for writer in self.writers.iter_mut() {
+            result = result.and(writer.write_str(s));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_to_all_writers() {
+        let mut writer = MultiWriter::new([String::new(), String::new(), String::new()]);
+        write!(writer, "boom {}", 42).unwrap();
+        assert_eq!(writer.into_inner(), ["boom 42", "boom 42", "boom 42"]);
+    }
+
+    #[test]
+    fn keeps_writing_after_one_writer_fails() {
+        /// Fails every write if it's read only
+        struct Output {
+            written: String,
+            read_only: bool,
+        }
+
+        impl Write for Output {
+            fn write_str(&mut self, s: &str) -> Result {
+                if self.read_only {
+                    return Err(core::fmt::Error);
+                }
+                self.written.write_str(s)
+            }
+        }
+
+        let output = |read_only| Output {
+            written: String::new(),
+            read_only,
+        };
+        let mut writer = MultiWriter::new([output(false), output(true), output(false)]);
+        assert!(writer.write_str("boom").is_err());
+        let written = writer.inner().iter().map(|output| output.written.as_str());
+        assert!(written.eq(["boom", "", "boom"]));
+    }
+}
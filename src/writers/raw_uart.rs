@@ -0,0 +1,77 @@
+use core::fmt::{Result, Write};
+
+/// Writer that drives a uart by accessing its registers directly, for panics that happen before
+/// the HAL is set up
+///
+/// Every byte is written by polling the status register until `status & tx_ready_mask` is
+/// non-zero, then writing the byte to the data register. Both registers are accessed with
+/// volatile 32 bit reads and writes. The uart has to be clocked and configured already, e.g. by
+/// the bootloader or a few register writes at the start of `main`.
+///
+/// ```ignore
+/// // USART1 on an stm32f1, TXE is bit 7 of the status register
+/// let uart = unsafe {
+///     RawUartWriter::new(0x4001_3804 as *mut u32, 0x4001_3800 as *mut u32, 1 << 7)
+/// };
+/// ```
+pub struct RawUartWriter {
+    data: *mut u32,
+    status: *mut u32,
+    tx_ready_mask: u32,
+}
+
+impl RawUartWriter {
+    /// Create a writer for the uart with the given data and status registers
+    ///
+    /// # Safety
+    ///
+    /// `data` and `status` must be valid for volatile reads and writes for as long as the writer
+    /// is used, and writing to the data register must not interfere with other code driving the
+    /// same uart, which usually means nothing else may use it.
+    pub const unsafe fn new(data: *mut u32, status: *mut u32, tx_ready_mask: u32) -> Self {
+        RawUartWriter {
+            data,
+            status,
+            tx_ready_mask,
+        }
+    }
+}
+
+// the registers belong to the peripheral, not to the thread that created the writer
+unsafe impl Send for RawUartWriter {}
+
+impl Write for RawUartWriter {
+    fn write_str(&mut self, s: &str) -> Result {
+        for byte in s.bytes() {
+            // safe because the registers are valid, as promised by the caller of `new`
+            unsafe {
+                while self.status.read_volatile() & self.tx_ready_mask == 0 {
+                    core::hint::spin_loop();
+                }
+                self.data.write_volatile(byte as u32);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::globals;
+    use crate::{simulate_panic_with, PanicHandler};
+
+    #[test]
+    fn writes_to_the_data_register_once_ready() {
+        let _globals = globals();
+        let mut data = 0;
+        let mut status = 0b100;
+        let uart = unsafe { RawUartWriter::new(&mut data, &mut status, 0b100) };
+        let handler = core::pin::pin!(PanicHandler::new(uart));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        drop(registration);
+        // the data register only holds the last byte
+        assert_eq!(data, u32::from(b'm'));
+    }
+}
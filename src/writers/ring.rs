@@ -0,0 +1,86 @@
+use core::fmt::{Result, Write};
+
+/// Writer that keeps the last `N` bytes written to it
+///
+/// Unlike [`SliceWriter`](crate::writers::SliceWriter), which drops new output once it's full,
+/// this overwrites the oldest output. Useful when the end of a long message matters most.
+pub struct RingWriter<const N: usize> {
+    buffer: [u8; N],
+    /// Index of the oldest byte
+    start: usize,
+    len: usize,
+}
+
+impl<const N: usize> RingWriter<N> {
+    pub fn new() -> Self {
+        RingWriter {
+            buffer: [0; N],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    /// The last `N` bytes written, oldest first
+    ///
+    /// Once older output has been overwritten the contents can start in the middle of a
+    /// character.
+    pub fn contents(&mut self) -> &[u8] {
+        // reorder the buffer so the contents are contiguous
+        self.buffer.rotate_left(self.start);
+        self.start = 0;
+        &self.buffer[..self.len]
+    }
+}
+
+impl<const N: usize> Default for RingWriter<N> {
+    fn default() -> Self {
+        RingWriter::new()
+    }
+}
+
+impl<const N: usize> Write for RingWriter<N> {
+    fn write_str(&mut self, s: &str) -> Result {
+        if N == 0 {
+            return Ok(());
+        }
+        for byte in s.bytes() {
+            self.buffer[(self.start + self.len) % N] = byte;
+            if self.len < N {
+                self.len += 1;
+            } else {
+                self.start = (self.start + 1) % N;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_everything_until_full() {
+        let mut writer = RingWriter::<8>::new();
+        writer.write_str("boom").unwrap();
+        assert_eq!(writer.contents(), b"boom");
+    }
+
+    #[test]
+    fn keeps_the_newest_bytes() {
+        let mut writer = RingWriter::<8>::new();
+        writer.write_str("panicked at ").unwrap();
+        writer.write_str("main.rs").unwrap();
+        assert_eq!(writer.contents(), b" main.rs");
+        // writing after reading the contents continues where it left off
+        writer.write_str(":42").unwrap();
+        assert_eq!(writer.contents(), b"in.rs:42");
+    }
+
+    #[test]
+    fn zero_capacity_drops_everything() {
+        let mut writer = RingWriter::<0>::new();
+        writer.write_str("boom").unwrap();
+        assert_eq!(writer.contents(), b"");
+    }
+}
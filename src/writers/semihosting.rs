@@ -0,0 +1,47 @@
+use core::fmt::{Error, Result, Write};
+use cortex_m_semihosting::hio::{self, HostStream};
+
+/// Writer for the host console using semihosting
+///
+/// Useful during development on QEMU or with a debug probe, without needing a UART:
+///
+/// ```ignore
+/// let handler = pin!(PanicHandler::new(SemihostingWriter::stdout()));
+/// let _registration = handler.register();
+/// ```
+///
+/// Run with `qemu-system-arm -machine lm3s6965evb -nographic -semihosting-config
+/// enable=on,target=native -kernel app`, the panic message will show up on the terminal.
+///
+/// Every write halts the core until the host has handled it, wrap the writer in a
+/// [`BufferedWriter`](crate::writers::BufferedWriter) to reduce the number of round trips. If the
+/// host stream can't be opened or a write fails the output is dropped. Note that semihosting
+/// calls fault when no debugger is attached.
+pub struct SemihostingWriter {
+    stream: Option<HostStream>,
+}
+
+impl SemihostingWriter {
+    /// Write to the host's stdout
+    pub fn stdout() -> Self {
+        SemihostingWriter {
+            stream: hio::hstdout().ok(),
+        }
+    }
+
+    /// Write to the host's stderr
+    pub fn stderr() -> Self {
+        SemihostingWriter {
+            stream: hio::hstderr().ok(),
+        }
+    }
+}
+
+impl Write for SemihostingWriter {
+    fn write_str(&mut self, s: &str) -> Result {
+        match &mut self.stream {
+            Some(stream) => stream.write_all(s.as_bytes()).map_err(|_| Error),
+            None => Err(Error),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{Flush, PanicWriter};
+use core::fmt::{Error, Result, Write};
+use embedded_hal_nb::nb::block;
+use embedded_hal_nb::serial;
+
+/// Writer for a serial port implementing the `embedded-hal-nb` serial traits
+///
+/// Every byte is written by blocking until the port accepts it. Use
+/// [`with_crlf`](SerialWriter::with_crlf) for terminals that need `\r\n` line endings.
+pub struct SerialWriter<S: serial::Write<u8>> {
+    serial: S,
+    crlf: bool,
+}
+
+impl<S: serial::Write<u8>> SerialWriter<S> {
+    pub fn new(serial: S) -> Self {
+        SerialWriter {
+            serial,
+            crlf: false,
+        }
+    }
+
+    /// Create a writer that translates `\n` into `\r\n`
+    pub fn with_crlf(serial: S) -> Self {
+        SerialWriter { serial, crlf: true }
+    }
+
+    pub fn inner(&mut self) -> &mut S {
+        &mut self.serial
+    }
+
+    pub fn into_inner(self) -> S {
+        self.serial
+    }
+
+    fn write_byte(&mut self, byte: u8) -> Result {
+        block!(self.serial.write(byte)).map_err(|_| Error)
+    }
+}
+
+impl<S: serial::Write<u8>> Write for SerialWriter<S> {
+    fn write_str(&mut self, s: &str) -> Result {
+        for byte in s.bytes() {
+            if self.crlf && byte == b'\n' {
+                self.write_byte(b'\r')?;
+            }
+            self.write_byte(byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: serial::Write<u8>> Flush for SerialWriter<S> {
+    fn flush(&mut self) {
+        let _ = block!(self.serial.flush());
+    }
+}
+
+impl<S: serial::Write<u8>> PanicWriter for SerialWriter<S> {
+    fn flush(&mut self) {
+        let _ = block!(self.serial.flush());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::globals;
+    use crate::{simulate_panic_with, PanicHandler, PanicInfo};
+    use core::convert::Infallible;
+    use embedded_hal_nb::nb;
+
+    /// Serial port that is busy every other time it's written to
+    #[derive(Default)]
+    struct MockSerial {
+        written: Vec<u8>,
+        busy: bool,
+        flushed: usize,
+    }
+
+    impl serial::ErrorType for MockSerial {
+        type Error = Infallible;
+    }
+
+    impl serial::Write<u8> for MockSerial {
+        fn write(&mut self, byte: u8) -> nb::Result<(), Infallible> {
+            self.busy = !self.busy;
+            if self.busy {
+                return Err(nb::Error::WouldBlock);
+            }
+            self.written.push(byte);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> nb::Result<(), Infallible> {
+            self.flushed += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn blocks_until_every_byte_is_written() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_panic_writer(
+            SerialWriter::with_crlf(MockSerial::default()),
+            |out: &mut SerialWriter<MockSerial>, info: &PanicInfo| {
+                let _ = write!(out, "panic\n{}\n", info.message());
+            }
+        ));
+        let mut registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(registration.inner().written, b"panic\r\nboom\r\n");
+        assert_eq!(registration.inner().flushed, 1);
+    }
+}
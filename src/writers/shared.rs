@@ -0,0 +1,108 @@
+use core::cell::RefCell;
+use core::fmt::{Error, Result, Write};
+use critical_section::Mutex;
+
+/// Writer for a peripheral that's shared with the rest of the application through a
+/// `critical_section::Mutex<RefCell<W>>`
+///
+/// Every write enters a critical section and borrows the writer. If the writer is already
+/// borrowed, because the panic happened while the application was using it, the write fails with
+/// `fmt::Error`. Use [`new_overriding`](SharedWriter::new_overriding) to write anyway in that
+/// case.
+///
+/// ```ignore
+/// static UART: Mutex<RefCell<Uart>> = Mutex::new(RefCell::new(Uart::new(UART0_BASE)));
+///
+/// let handler = pin!(PanicHandler::new(SharedWriter::new(&UART)));
+/// ```
+pub struct SharedWriter<W: Write + 'static> {
+    inner: &'static Mutex<RefCell<W>>,
+    /// Whether a held borrow is overridden during a panic
+    overriding: bool,
+}
+
+impl<W: Write + 'static> SharedWriter<W> {
+    pub const fn new(inner: &'static Mutex<RefCell<W>>) -> Self {
+        SharedWriter {
+            inner,
+            overriding: false,
+        }
+    }
+
+    /// Create a writer that overrides a held borrow while a panic is being handled
+    ///
+    /// The panic message then still gets out if the panic happened while the application was
+    /// using the writer, possibly in the middle of the output that was being written. Outside of a
+    /// panic, see [`is_panicking`](crate::is_panicking), a held borrow still makes the write fail.
+    ///
+    /// # Safety
+    ///
+    /// Overriding the borrow creates a second mutable reference to the writer. The code holding
+    /// the borrow must never use the writer again once a panic started, which holds as long as
+    /// the panic handler doesn't return, so no resume function may be set with
+    /// [`set_resume`](crate::set_resume).
+    pub const unsafe fn new_overriding(inner: &'static Mutex<RefCell<W>>) -> Self {
+        SharedWriter {
+            inner,
+            overriding: true,
+        }
+    }
+
+    pub fn inner(&self) -> &'static Mutex<RefCell<W>> {
+        self.inner
+    }
+}
+
+impl<W: Write + 'static> Write for SharedWriter<W> {
+    fn write_str(&mut self, s: &str) -> Result {
+        critical_section::with(|cs| {
+            let cell = self.inner.borrow(cs);
+            match cell.try_borrow_mut() {
+                Ok(mut writer) => writer.write_str(s),
+                // the borrow is held by code that was interrupted by the panic, or by the
+                // panicking code itself, the caller of `new_overriding` promised neither touches
+                // the writer again
+                Err(_) if self.overriding && crate::is_panicking() => unsafe {
+                    (*cell.as_ptr()).write_str(s)
+                },
+                Err(_) => Err(Error),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::globals;
+    use crate::{simulate_panic_with, PanicHandler};
+
+    #[test]
+    fn writes_through_the_mutex() {
+        static UART: Mutex<RefCell<String>> = Mutex::new(RefCell::new(String::new()));
+
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new(SharedWriter::new(&UART)));
+        let _registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        critical_section::with(|cs| {
+            assert_eq!(*UART.borrow(cs).borrow(), "panicked at :\nboom");
+        });
+    }
+
+    #[test]
+    fn held_borrow_fails_the_write() {
+        static UART: Mutex<RefCell<String>> = Mutex::new(RefCell::new(String::new()));
+
+        let mut writer = SharedWriter::new(&UART);
+        // an overriding writer only overrides the borrow during a panic
+        let mut overriding = unsafe { SharedWriter::new_overriding(&UART) };
+        critical_section::with(|cs| {
+            let _held = UART.borrow(cs).borrow_mut();
+            assert_eq!(writer.write_str("boom"), Err(Error));
+            assert_eq!(overriding.write_str("boom"), Err(Error));
+        });
+        assert_eq!(writer.write_str("ok"), Ok(()));
+        critical_section::with(|cs| assert_eq!(*UART.borrow(cs).borrow(), "ok"));
+    }
+}
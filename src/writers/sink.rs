@@ -0,0 +1,42 @@
+use core::fmt::{Result, Write};
+
+/// Writer that discards everything written to it
+///
+/// For handlers that only need a side effect and a post-panic action, e.g.
+/// `PanicHandler::new_with_side_effect(Sink, location_hook, blink_led)`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Sink;
+
+impl Write for Sink {
+    fn write_str(&mut self, _s: &str) -> Result {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::location_hook;
+    use crate::tests::globals;
+    use crate::{simulate_panic_with, PanicHandler, PanicInfo};
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn only_the_side_effect_is_observable() {
+        static BLINKED: AtomicBool = AtomicBool::new(false);
+
+        fn blink_led(_info: &PanicInfo) {
+            BLINKED.store(true, Ordering::Release);
+        }
+
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_side_effect(
+            Sink,
+            location_hook,
+            blink_led
+        ));
+        let _registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert!(BLINKED.load(Ordering::Acquire));
+    }
+}
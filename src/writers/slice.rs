@@ -0,0 +1,147 @@
+use crate::{HandlerError, Validate};
+use core::fmt::{Result, Write};
+
+/// Writer that captures output into a fixed buffer
+///
+/// Once the buffer is full any further output is silently dropped, use
+/// [`with_marker`](SliceWriter::with_marker) to make the truncation visible.
+pub struct SliceWriter<'a> {
+    buffer: &'a mut [u8],
+    position: usize,
+    truncated: bool,
+    marker: &'static str,
+}
+
+impl<'a> SliceWriter<'a> {
+    pub const fn new(buffer: &'a mut [u8]) -> Self {
+        SliceWriter::with_marker(buffer, "")
+    }
+
+    /// Create a writer that ends the output with `marker`, e.g. `"...[truncated]"`, when it
+    /// doesn't fit in the buffer
+    ///
+    /// Room for the marker is reserved at the end of the buffer, so the output is limited to the
+    /// buffer length minus the marker length.
+    pub const fn with_marker(buffer: &'a mut [u8], marker: &'static str) -> Self {
+        SliceWriter {
+            buffer,
+            position: 0,
+            truncated: false,
+            marker,
+        }
+    }
+
+    /// The bytes written so far
+    pub fn written(&self) -> &[u8] {
+        &self.buffer[..self.position]
+    }
+
+    /// The bytes written, for the full lifetime of the buffer
+    pub fn into_written(self) -> &'a [u8] {
+        &self.buffer[..self.position]
+    }
+
+    /// Whether any output was dropped because the buffer was full
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn copy(&mut self, s: &str, limit: usize) -> usize {
+        let remaining = &mut self.buffer[self.position..limit];
+        let len = s.len().min(remaining.len());
+        remaining[..len].copy_from_slice(&s.as_bytes()[..len]);
+        self.position += len;
+        len
+    }
+}
+
+impl<'a> Write for SliceWriter<'a> {
+    fn write_str(&mut self, s: &str) -> Result {
+        if self.truncated {
+            return Ok(());
+        }
+        if self.marker.is_empty() {
+            let len = self.copy(s, self.buffer.len());
+            self.truncated = len < s.len();
+            return Ok(());
+        }
+
+        let limit = self.buffer.len().saturating_sub(self.marker.len());
+        if s.len() <= limit - self.position {
+            self.copy(s, limit);
+        } else {
+            // don't cut a character in half so the output stays valid utf-8
+            let mut len = limit - self.position;
+            while !s.is_char_boundary(len) {
+                len -= 1;
+            }
+            self.copy(&s[..len], limit);
+            self.copy(self.marker, self.buffer.len());
+            self.truncated = true;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Validate for SliceWriter<'a> {
+    /// Fails if the buffer has no room left after reserving space for the marker
+    fn validate(&self) -> core::result::Result<(), HandlerError> {
+        if self.buffer.len() <= self.marker.len() {
+            return Err(HandlerError::NoCapacity);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_the_output() {
+        let mut buffer = [0; 16];
+        let mut writer = SliceWriter::new(&mut buffer);
+        write!(writer, "boom {}", 42).unwrap();
+        assert_eq!(writer.written(), b"boom 42");
+        assert!(!writer.truncated());
+    }
+
+    #[test]
+    fn drops_what_doesnt_fit() {
+        let mut buffer = [0; 6];
+        let mut writer = SliceWriter::new(&mut buffer);
+        writer.write_str("boom").unwrap();
+        writer.write_str(" at main.rs").unwrap();
+        writer.write_str("!").unwrap();
+        assert!(writer.truncated());
+        assert_eq!(writer.written(), b"boom a");
+    }
+
+    #[test]
+    fn ends_truncated_output_with_the_marker() {
+        let mut buffer = [0; 12];
+        let mut writer = SliceWriter::with_marker(&mut buffer, "...");
+        writer.write_str("boom").unwrap();
+        writer.write_str(" at main.rs").unwrap();
+        writer.write_str("!").unwrap();
+        assert!(writer.truncated());
+        assert_eq!(writer.written(), b"boom at m...");
+    }
+
+    #[test]
+    fn marker_doesnt_split_a_char() {
+        let mut buffer = [0; 6];
+        let mut writer = SliceWriter::with_marker(&mut buffer, "..");
+        writer.write_str("aaaä").unwrap();
+        assert_eq!(writer.written(), "aaa..".as_bytes());
+    }
+
+    #[test]
+    fn output_that_fits_has_no_marker() {
+        let mut buffer = [0; 8];
+        let mut writer = SliceWriter::with_marker(&mut buffer, "...");
+        writer.write_str("boom").unwrap();
+        assert!(!writer.truncated());
+        assert_eq!(writer.written(), b"boom");
+    }
+}
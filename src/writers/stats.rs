@@ -0,0 +1,99 @@
+use core::fmt::{Result, Write};
+
+/// Number of calls and bytes recorded by a [`StatsWriter`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// Number of `write_str` calls, including failed ones
+    pub calls: usize,
+    /// Number of bytes successfully written
+    pub bytes: usize,
+}
+
+/// Writer that records how often it's called and how much is written through it
+///
+/// Unlike [`CountingWriter`](crate::writers::CountingWriter) this also counts the calls, which
+/// is useful for comparing how hooks split up their output.
+pub struct StatsWriter<W: Write> {
+    inner: W,
+    stats: Stats,
+}
+
+impl<W: Write> StatsWriter<W> {
+    pub fn new(inner: W) -> Self {
+        StatsWriter {
+            inner,
+            stats: Stats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    pub fn inner(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for StatsWriter<W> {
+    fn write_str(&mut self, s: &str) -> Result {
+        self.stats.calls += 1;
+        self.inner.write_str(s)?;
+        self.stats.bytes += s.len();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::globals;
+    use crate::writers::SliceWriter;
+    use crate::{simulate_panic_with, PanicHandler, PanicInfo};
+
+    #[test]
+    fn counts_the_calls_of_a_hook() {
+        let _globals = globals();
+        let handler = core::pin::pin!(PanicHandler::new_with_hook(
+            StatsWriter::new(String::new()),
+            |out: &mut StatsWriter<String>, info: &PanicInfo| {
+                let _ = out.write_str("panic: ");
+                let _ = out.write_str(info.message().as_str().unwrap());
+            }
+        ));
+        let registration = handler.register();
+        simulate_panic_with(format_args!("boom"), None);
+        assert_eq!(
+            registration.stats(),
+            Stats {
+                calls: 2,
+                bytes: 11
+            }
+        );
+    }
+
+    /// Fails every write
+    struct Failing;
+
+    impl Write for Failing {
+        fn write_str(&mut self, _: &str) -> Result {
+            Err(core::fmt::Error)
+        }
+    }
+
+    #[test]
+    fn failed_writes_are_calls_without_bytes() {
+        let mut writer = StatsWriter::new(Failing);
+        assert!(writer.write_str("boom").is_err());
+        assert_eq!(writer.stats(), Stats { calls: 1, bytes: 0 });
+        // bytes dropped by the inner writer still count, it didn't report an error
+        let mut buffer = [0; 2];
+        let mut writer = StatsWriter::new(SliceWriter::new(&mut buffer));
+        writer.write_str("boom").unwrap();
+        assert_eq!(writer.stats(), Stats { calls: 1, bytes: 4 });
+    }
+}
@@ -0,0 +1,63 @@
+use core::fmt::{Result, Write};
+
+/// Writer that forwards everything written to two inner writers
+///
+/// A failure in one writer doesn't stop the other writer from being written to.
+pub struct TeeWriter<A: Write, B: Write> {
+    a: A,
+    b: B,
+}
+
+impl<A: Write, B: Write> TeeWriter<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        TeeWriter { a, b }
+    }
+
+    pub fn inner_a(&mut self) -> &mut A {
+        &mut self.a
+    }
+
+    pub fn inner_b(&mut self) -> &mut B {
+        &mut self.b
+    }
+
+    pub fn into_inner(self) -> (A, B) {
+        (self.a, self.b)
+    }
+}
+
+impl<A: Write, B: Write> Write for TeeWriter<A, B> {
+    fn write_str(&mut self, s: &str) -> Result {
+        let a = self.a.write_str(s);
+        let b = self.b.write_str(s);
+        a.and(b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Error;
+
+    struct Failing;
+
+    impl Write for Failing {
+        fn write_str(&mut self, _s: &str) -> Result {
+            Err(Error)
+        }
+    }
+
+    #[test]
+    fn writes_to_both_writers() {
+        let mut writer = TeeWriter::new(String::new(), String::new());
+        write!(writer, "boom {}", 42).unwrap();
+        assert_eq!(writer.into_inner(), ("boom 42".into(), "boom 42".into()));
+    }
+
+    #[test]
+    fn keeps_writing_after_one_writer_fails() {
+        let mut writer = TeeWriter::new(Failing, String::new());
+        assert!(writer.write_str("boom").is_err());
+        assert_eq!(writer.inner_b(), "boom");
+    }
+}
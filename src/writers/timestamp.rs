@@ -0,0 +1,73 @@
+use core::fmt::{Result, Write};
+
+/// Writer that starts every line with the current tick of a clock, e.g. `[1042] `
+///
+/// The clock is read when the first character of a line is written, so each line carries the
+/// time it was started at. Unlike the [`clock`](crate::PanicHandlerBuilder::clock) option, which
+/// timestamps the panic once, this allows interleaving the panic with other logs line by line.
+pub struct TimestampWriter<W: Write, C: Fn() -> u64> {
+    inner: W,
+    clock: C,
+    line_start: bool,
+}
+
+impl<W: Write, C: Fn() -> u64> TimestampWriter<W, C> {
+    pub fn new(inner: W, clock: C) -> Self {
+        TimestampWriter {
+            inner,
+            clock,
+            line_start: true,
+        }
+    }
+
+    pub fn inner(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write, C: Fn() -> u64> Write for TimestampWriter<W, C> {
+    fn write_str(&mut self, s: &str) -> Result {
+        let mut rest = s;
+        while !rest.is_empty() {
+            if self.line_start {
+                write!(self.inner, "[{}] ", (self.clock)())?;
+                self.line_start = false;
+            }
+            let end = rest.find('\n').map_or(rest.len(), |end| end + 1);
+            let (line, next) = rest.split_at(end);
+            self.inner.write_str(line)?;
+            self.line_start = line.ends_with('\n');
+            rest = next;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::globals;
+    use crate::{simulate_panic_with, PanicHandler};
+    use std::cell::Cell;
+
+    #[test]
+    fn reads_the_clock_at_the_start_of_every_line() {
+        let _globals = globals();
+        let ticks = Cell::new(0);
+        let clock = || {
+            ticks.set(ticks.get() + 1);
+            ticks.get()
+        };
+        let handler = core::pin::pin!(PanicHandler::new(TimestampWriter::new(
+            String::new(),
+            clock
+        )));
+        let mut registration = handler.register();
+        simulate_panic_with(format_args!("one\ntwo"), None);
+        assert_eq!(registration.inner(), "[1] panicked at :\n[2] one\n[3] two");
+    }
+}
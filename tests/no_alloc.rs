@@ -0,0 +1,31 @@
+//! Build the `no_alloc` example for a bare metal target, which fails to link if the crate
+//! needs an allocator
+
+use std::process::Command;
+
+const TARGET: &str = "thumbv7em-none-eabihf";
+
+#[test]
+fn links_without_an_allocator() {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".into());
+    // a separate target directory, the one of the test run is locked while it runs
+    let target_dir = concat!(env!("CARGO_TARGET_TMPDIR"), "/no-alloc");
+    let output = Command::new(cargo)
+        .args([
+            "build",
+            "--example",
+            "no_alloc",
+            "--features",
+            "no-alloc-test",
+        ])
+        .args(["--target", TARGET, "--target-dir", target_dir])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to run cargo");
+    assert!(
+        output.status.success(),
+        "building the no_alloc example for {} failed:\n{}",
+        TARGET,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
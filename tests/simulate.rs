@@ -0,0 +1,32 @@
+//! Runs a handler through the public `simulate` api, the way the tests of a user would
+
+use core::fmt::Write;
+use core::panic::Location;
+use panic_write::{simulate_panic, PanicAction, PanicHandler, PanicInfo};
+
+fn hook(out: &mut String, info: &PanicInfo) {
+    let _ = writeln!(
+        out,
+        "{}: {}",
+        info.location().unwrap().line(),
+        info.message()
+    );
+}
+
+// the registered handler is global, so everything is done in a single test
+#[test]
+fn runs_the_hook_for_every_simulated_panic() {
+    let handler = core::pin::pin!(PanicHandler::new_with_hook(String::new(), hook));
+    let registration = handler.register();
+    let location = Location::caller();
+
+    let action = simulate_panic(&PanicInfo::new(format_args!("boom"), Some(location)));
+    assert_eq!(action, PanicAction::SpinLoop);
+    let action = simulate_panic(&PanicInfo::new(format_args!("boom {}", 42), Some(location)));
+    assert_eq!(action, PanicAction::SpinLoop);
+
+    assert_eq!(
+        *registration,
+        format!("{0}: boom\n{0}: boom 42\n", location.line())
+    );
+}
@@ -0,0 +1,139 @@
+//! Build the `size_one` and `size_three` examples for a bare metal target and compare their code
+//! size, to catch the handler code being instantiated for every writer type again
+//!
+//! Writing the panic is shared by all handlers, only a small part is instantiated for every
+//! writer type. Before that each additional writer type added about 770 bytes of `.text`.
+
+use std::convert::TryInto;
+use std::path::PathBuf;
+use std::process::Command;
+
+const TARGET: &str = "thumbv7em-none-eabihf";
+
+/// The most `.text` a handler for another writer type may add
+const PER_WRITER_BUDGET: u64 = 640;
+
+/// Build an example in the release profile used for the size figures in the README
+fn build(example: &str) -> Vec<u8> {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".into());
+    // a separate target directory, the one of the test run is locked while it runs
+    let target_dir = concat!(env!("CARGO_TARGET_TMPDIR"), "/size");
+    let output = Command::new(cargo)
+        .args(["build", "--release", "--example", example])
+        .args(["--features", "size-test"])
+        .args(["--config", "profile.release.opt-level=\"s\""])
+        .args(["--config", "profile.release.lto=true"])
+        .args(["--target", TARGET, "--target-dir", target_dir])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("failed to run cargo");
+    assert!(
+        output.status.success(),
+        "building the {} example for {} failed:\n{}",
+        example,
+        TARGET,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let path: PathBuf = [target_dir, TARGET, "release", "examples", example]
+        .iter()
+        .collect();
+    std::fs::read(path).unwrap()
+}
+
+/// The sections and symbols of a 32 bit little endian ELF file
+struct Elf<'a> {
+    data: &'a [u8],
+}
+
+/// A section header, only the fields that are needed
+struct Section {
+    name: u32,
+    offset: usize,
+    size: usize,
+    link: usize,
+}
+
+impl<'a> Elf<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        assert_eq!(&data[..4], b"\x7fELF", "not an ELF file");
+        assert_eq!(data[4], 1, "not a 32 bit ELF file");
+        assert_eq!(data[5], 1, "not a little endian ELF file");
+        Elf { data }
+    }
+
+    fn u16(&self, offset: usize) -> usize {
+        u16::from_le_bytes(self.data[offset..offset + 2].try_into().unwrap()).into()
+    }
+
+    fn u32(&self, offset: usize) -> usize {
+        u32::from_le_bytes(self.data[offset..offset + 4].try_into().unwrap()) as usize
+    }
+
+    fn sections(&self) -> Vec<Section> {
+        let (offset, size, count) = (self.u32(0x20), self.u16(0x2e), self.u16(0x30));
+        (0..count)
+            .map(|index| {
+                let header = offset + index * size;
+                Section {
+                    name: self.u32(header) as u32,
+                    offset: self.u32(header + 16),
+                    size: self.u32(header + 20),
+                    link: self.u32(header + 24),
+                }
+            })
+            .collect()
+    }
+
+    /// The nul terminated string at `offset` in the string table `table`
+    fn string(&self, table: &Section, offset: usize) -> &'a str {
+        let start = table.offset + offset;
+        let len = self.data[start..].iter().position(|&b| b == 0).unwrap();
+        std::str::from_utf8(&self.data[start..start + len]).unwrap()
+    }
+
+    fn section(&self, name: &str) -> Section {
+        let mut sections = self.sections();
+        let names = sections.swap_remove(self.u16(0x32));
+        sections
+            .into_iter()
+            .find(|section| self.string(&names, section.name as usize) == name)
+            .unwrap_or_else(|| panic!("no {} section", name))
+    }
+
+    /// The names of all symbols in the symbol table
+    fn symbols(&self) -> Vec<&'a str> {
+        let symtab = self.section(".symtab");
+        let strtab = &self.sections()[symtab.link];
+        (0..symtab.size / 16)
+            .map(|index| self.string(strtab, self.u32(symtab.offset + index * 16)))
+            .collect()
+    }
+}
+
+#[test]
+fn handlers_for_more_writer_types_share_the_code_writing_the_panic() {
+    let (one, three) = (build("size_one"), build("size_three"));
+    let (one, three) = (Elf::new(&one), Elf::new(&three));
+
+    // the bulk of the handler exists once, however many writer types there are
+    for elf in [&one, &three] {
+        let shared = elf
+            .symbols()
+            .into_iter()
+            .filter(|name| name.contains("write_panic_dyn"))
+            .count();
+        assert_eq!(shared, 1, "write_panic_dyn should be a single function");
+    }
+
+    let (one, three) = (
+        one.section(".text").size as u64,
+        three.section(".text").size as u64,
+    );
+    assert!(
+        three - one <= 2 * PER_WRITER_BUDGET,
+        "two more writer types grew .text from {} to {} bytes, more than {} per writer type",
+        one,
+        three,
+        PER_WRITER_BUDGET
+    );
+}
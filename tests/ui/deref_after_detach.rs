@@ -0,0 +1,11 @@
+use core::fmt::Write;
+use core::pin::pin;
+use panic_write::PanicHandler;
+
+fn main() {
+    let mut handler = pin!(PanicHandler::new(String::new()));
+    let mut registration = handler.as_mut().register();
+    let _writer = registration.detach();
+    // the registration is consumed by detach, the moved out writer can't be reached through it
+    let _ = registration.write_str("after detach");
+}
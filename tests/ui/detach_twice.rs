@@ -0,0 +1,9 @@
+use core::pin::pin;
+use panic_write::PanicHandler;
+
+fn main() {
+    let mut handler = pin!(PanicHandler::new(String::new()));
+    let registration = handler.as_mut().register();
+    let _writer = registration.detach();
+    let _again = registration.detach();
+}
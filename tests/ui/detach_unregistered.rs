@@ -0,0 +1,8 @@
+use core::pin::pin;
+use panic_write::PanicHandler;
+
+fn main() {
+    let mut handler = pin!(PanicHandler::new(String::new()));
+    // only a registered handler can be detached, through its registration
+    let _writer = PanicHandler::detach(handler.as_mut());
+}
@@ -0,0 +1,8 @@
+use panic_write::PanicHandler;
+
+fn main() {
+    let handler = Box::leak(Box::new(PanicHandler::new(String::new())));
+    let handler = PanicHandler::register_static(handler);
+    // the writer of a registered handler can't be borrowed while the panic handler can reach it
+    handler.get_inner().push_str("starting app");
+}
@@ -0,0 +1,10 @@
+use core::pin::pin;
+use panic_write::PanicHandler;
+
+fn main() {
+    let mut handler = pin!(PanicHandler::new(String::new()));
+    let registration = handler.as_mut().register();
+    // the writer of a registered handler is only reachable through the registration
+    handler.as_mut().get_inner().push_str("starting app");
+    drop(registration);
+}
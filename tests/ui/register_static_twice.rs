@@ -0,0 +1,8 @@
+use panic_write::PanicHandler;
+
+fn main() {
+    let handler = Box::leak(Box::new(PanicHandler::new(String::new())));
+    let handler = PanicHandler::register_static(handler);
+    // a registered handler can't be registered again
+    let _again = handler.register();
+}
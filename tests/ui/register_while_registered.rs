@@ -0,0 +1,10 @@
+use core::pin::pin;
+use panic_write::PanicHandler;
+
+fn main() {
+    let mut handler = pin!(PanicHandler::new(String::new()));
+    let registration = handler.as_mut().register();
+    // the handler is borrowed by the registration, it can't be registered a second time
+    let _again = handler.as_mut().register();
+    drop(registration);
+}
@@ -0,0 +1,12 @@
+use core::pin::pin;
+use panic_write::{PanicInfo, PanicHandler};
+
+fn hook(_writer: &mut String, _info: &PanicInfo) {}
+
+fn main() {
+    let mut handler = pin!(PanicHandler::new(String::new()));
+    let registration = handler.as_mut().register();
+    // replacing the hook of a registered handler goes through the registration
+    handler.as_mut().set_hook(hook);
+    drop(registration);
+}